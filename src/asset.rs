@@ -0,0 +1,260 @@
+//! Parsing asset IDs into base, quote and suffix, and inferring their category.
+
+use std::fmt;
+use strsim::jaro_winkler;
+
+/// Quote currencies recognised at the end of asset IDs. Checked in order, so list
+/// longer codes before any code they end with.
+const KNOWN_QUOTES: &[&str] = &[
+    "USDT", "USDC", "USD", "EUR", "GBP", "JPY", "CHF", "CAD", "AUD", "BTC", "ETH",
+];
+
+/// Fiat currencies; a pair of two of them is FX
+const FIAT_CURRENCIES: &[&str] = &[
+    "USD", "EUR", "GBP", "JPY", "CHF", "CAD", "AUD", "NZD", "CNY", "CNH", "HKD", "SGD",
+    "KRW", "INR", "MXN", "BRL", "ZAR", "TRY", "SEK", "NOK", "DKK", "PLN",
+];
+
+/// Spot metals, which make a pair FX when used as the base
+const METALS: &[&str] = &["XAU", "XAG", "XPT", "XPD"];
+
+/// Stock and ETF tickers listed by Stork
+const EQUITY_BASES: &[&str] = &[
+    "AAPL", "MSFT", "NVDA", "AMZN", "GOOG", "GOOGL", "META", "TSLA", "NFLX", "AMD",
+    "COIN", "MSTR", "HOOD", "SPY", "QQQ",
+];
+
+/// ID suffixes marking feeds derived from rates rather than traded prices, e.g. `SOLUSD_RR`
+const RATE_SUFFIXES: &[&str] = &["RR", "APR", "APY", "FUNDING"];
+
+/// Kind of market an asset ID belongs to, inferred from its base and suffix
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    /// Cryptocurrencies, the default for anything not matched by another category
+    Crypto,
+    /// Pairs of two fiat currencies, and spot metals
+    Fx,
+    /// Stocks and ETFs
+    Equity,
+    /// Feeds derived from rates, such as `SOLUSD_RR`
+    Rate,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Category::Crypto => "crypto",
+            Category::Fx => "fx",
+            Category::Equity => "equity",
+            Category::Rate => "rate",
+        })
+    }
+}
+
+/// A Stork asset ID split into its parts, e.g. `SOLUSD_RR` -> base `SOL`, quote `USD`, suffix `RR`
+#[derive(Clone)]
+pub struct Asset {
+    /// The full asset ID
+    pub id: String,
+    /// The base asset, or the whole ID before any suffix if no quote currency was found
+    pub base: String,
+    /// `None` if the ID doesn't end with a known quote currency
+    pub quote: Option<String>,
+    /// The part after `_`, if any
+    pub suffix: Option<String>,
+    /// Kind of market, inferred from the other parts
+    pub category: Category,
+}
+
+impl Asset {
+    /// Split an asset ID into its parts, trying the known quote currencies in turn
+    pub fn parse(asset_id: &str) -> Self {
+        KNOWN_QUOTES.iter()
+            .find_map(|quote| Self::parse_with_quote(asset_id, quote))
+            .or_else(|| Self::parse_fiat_pair(asset_id))
+            .unwrap_or_else(|| Self::build(asset_id, split_suffix(asset_id).0, None))
+    }
+
+    /// Parse assuming `quote` is the quote currency, for quotes missing from the known table.
+    /// Returns `None` if the ID doesn't end with it.
+    pub fn parse_with_quote(asset_id: &str, quote: &str) -> Option<Self> {
+        let (pair, _) = split_suffix(asset_id);
+        pair.strip_suffix(quote)
+            .filter(|base| !base.is_empty())
+            .map(|base| Self::build(asset_id, base, Some(quote)))
+    }
+
+    /// Parse a pair of two fiat currencies quoted in one missing from the known table, e.g. `USDMXN`
+    fn parse_fiat_pair(asset_id: &str) -> Option<Self> {
+        FIAT_CURRENCIES.iter()
+            .filter_map(|quote| Self::parse_with_quote(asset_id, quote))
+            .find(|asset| FIAT_CURRENCIES.contains(&asset.base.as_str()))
+    }
+
+    fn build(asset_id: &str, base: &str, quote: Option<&str>) -> Self {
+        let suffix = split_suffix(asset_id).1;
+        let is_fiat_pair = FIAT_CURRENCIES.contains(&base)
+            && quote.is_none_or(|quote| FIAT_CURRENCIES.contains(&quote));
+        let category = if suffix.is_some_and(|suffix| RATE_SUFFIXES.contains(&suffix)) {
+            Category::Rate
+        } else if is_fiat_pair || METALS.contains(&base) {
+            Category::Fx
+        } else if EQUITY_BASES.contains(&base) {
+            Category::Equity
+        } else {
+            Category::Crypto
+        };
+        Self {
+            id: asset_id.to_string(),
+            base: base.to_string(),
+            quote: quote.map(str::to_string),
+            suffix: suffix.map(str::to_string),
+            category,
+        }
+    }
+}
+
+/// Split `SOLUSD_RR` into (`SOLUSD`, `RR`)
+fn split_suffix(asset_id: &str) -> (&str, Option<&str>) {
+    match asset_id.split_once('_') {
+        Some((pair, suffix)) => (pair, Some(suffix)),
+        None => (asset_id, None),
+    }
+}
+
+/// Filters for `get-assets`. Each list matches any of its values; empty lists match everything.
+#[derive(Default)]
+pub struct AssetFilter {
+    /// Quote currencies to keep
+    pub quotes: Vec<String>,
+    /// Base assets to keep
+    pub bases: Vec<String>,
+    /// Categories to keep
+    pub categories: Vec<Category>,
+    /// Hide suffixed variants such as `SOLUSD_RR`
+    pub no_variants: bool,
+}
+
+impl AssetFilter {
+    /// Whether the asset passes every filter
+    pub fn matches(&self, asset: &Asset) -> bool {
+        (self.quotes.is_empty() || asset.quote.as_ref().is_some_and(|quote| self.quotes.contains(quote)))
+            && (self.bases.is_empty() || self.bases.contains(&asset.base))
+            && (self.categories.is_empty() || self.categories.contains(&asset.category))
+            && !(self.no_variants && asset.suffix.is_some())
+    }
+}
+
+/// Available assets whose IDs look like `target`, for suggesting alternatives to a mistyped ID.
+/// Returns every asset containing `target` (at most 10) or, failing that, the `limit` closest matches.
+pub fn find_similar_assets(target: &str, available_assets: &[String], limit: usize) -> Vec<String> {
+    const HARD_LIMIT: usize = 10;  // Maximum number of results we'll ever return
+    let target = target.to_uppercase();
+    
+    // First, collect all direct substring matches (up to HARD_LIMIT)
+    let mut exact_matches: Vec<String> = available_assets.iter()
+        .filter(|asset| asset.to_uppercase().contains(&target))
+        .take(HARD_LIMIT)  // Never return more than HARD_LIMIT matches
+        .cloned()
+        .collect();
+    
+    // If we have any exact matches, return them (already limited to HARD_LIMIT)
+    if !exact_matches.is_empty() {
+        return exact_matches;
+    }
+    
+    // For remaining slots, find the best partial matches
+    // (excluding assets that were already exact matches)
+    let remaining_slots = limit.min(HARD_LIMIT);  // Use the smaller of limit or HARD_LIMIT
+    let mut partial_matches: Vec<(f64, String)> = available_assets.iter()
+        .filter(|asset| !asset.to_uppercase().contains(&target))
+        .map(|asset| {
+            let asset_upper = asset.to_uppercase();
+            let mut score = jaro_winkler(&target, &asset_upper);
+            
+            // Boost score for prefix/suffix matches
+            if asset_upper.starts_with(&target) {
+                score += 0.3;  // Boost for prefix match
+            } else if asset_upper.ends_with(&target) {
+                score += 0.2;  // Boost for suffix match
+            }
+            
+            // Boost for partial word matches
+            if target.len() >= 3 && asset_upper.contains(&target[..target.len()-1]) {
+                score += 0.1;  // Small boost for almost containing the target
+            }
+            
+            (score, asset.clone())
+        })
+        .collect();
+    
+    // Sort partial matches by score
+    partial_matches.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    
+    // Add the best partial matches up to the remaining_slots
+    exact_matches.extend(
+        partial_matches.into_iter()
+            .take(remaining_slots)
+            .map(|(_, asset)| asset)
+    );
+    
+    exact_matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn categories() {
+        let cases = [
+            ("BTCUSD", Category::Crypto),
+            ("ETHUSDT", Category::Crypto),
+            ("ETHBTC", Category::Crypto),
+            ("DOGEUSD", Category::Crypto),
+            ("SOLUSD_RR", Category::Rate),
+            ("USDJPY", Category::Fx),
+            ("USDMXN", Category::Fx),
+            ("EURUSD", Category::Fx),
+            ("XAUUSD", Category::Fx),
+            ("AAPLUSD", Category::Equity),
+        ];
+        for (asset_id, expected) in cases {
+            let category = Asset::parse(asset_id).category;
+            assert!(category == expected, "{} is {}, expected {}", asset_id, category, expected);
+        }
+    }
+
+    #[test]
+    fn parts() {
+        let cases = [
+            ("BTCUSD", "BTC", Some("USD"), None),
+            ("ETHUSDT", "ETH", Some("USDT"), None),
+            ("ETHBTC", "ETH", Some("BTC"), None),
+            ("SOLUSD_RR", "SOL", Some("USD"), Some("RR")),
+            ("USDJPY", "USD", Some("JPY"), None),
+            ("USDMXN", "USD", Some("MXN"), None),
+            ("XAUUSD", "XAU", Some("USD"), None),
+            ("AAPLUSD", "AAPL", Some("USD"), None),
+            ("WEIRD", "WEIRD", None, None),
+            ("WEIRD_APY", "WEIRD", None, Some("APY")),
+        ];
+        for (asset_id, base, quote, suffix) in cases {
+            let asset = Asset::parse(asset_id);
+            assert_eq!(
+                (asset.base.as_str(), asset.quote.as_deref(), asset.suffix.as_deref()),
+                (base, quote, suffix),
+                "{}",
+                asset_id,
+            );
+            assert_eq!(asset.id, asset_id);
+        }
+    }
+
+    #[test]
+    fn quote_missing_from_table() {
+        assert!(Asset::parse_with_quote("BTCUSDE", "USDE").is_some_and(|asset| asset.base == "BTC"));
+        assert!(Asset::parse_with_quote("BTCUSD", "USDE").is_none());
+        assert!(Asset::parse_with_quote("USDE", "USDE").is_none());
+    }
+}
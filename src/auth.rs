@@ -0,0 +1,336 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use stork_asset::client::{DEV_BASE_URL, PROD_BASE_URL};
+
+pub const CONFIG_DIR_NAME: &str = "stork-asset";
+const AUTH_FILE_NAME: &str = "auth.yaml";
+const KEYRING_SERVICE: &str = "stork-asset";
+
+pub const TOKEN_ENV_VAR: &str = "STORK_AUTH_TOKEN";
+pub const BASE_URL_ENV_VAR: &str = "STORK_REST_URL";
+
+pub const DEFAULT_PROFILE: &str = "default";
+pub const DEV_PROFILE: &str = "dev";
+
+/// Settings for one Stork environment / account. Unset fields fall back to the built-in defaults.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct Profile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_period_sec: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percent_change_threshold: Option<f64>,
+}
+
+impl Profile {
+    /// Apply any fields set in `other` on top of this profile
+    fn merged_with(mut self, other: &Profile) -> Self {
+        if other.auth_token.is_some() {
+            self.auth_token = other.auth_token.clone();
+        }
+        if other.base_url.is_some() {
+            self.base_url = other.base_url.clone();
+        }
+        if other.fallback_period_sec.is_some() {
+            self.fallback_period_sec = other.fallback_period_sec;
+        }
+        if other.percent_change_threshold.is_some() {
+            self.percent_change_threshold = other.percent_change_threshold;
+        }
+        self
+    }
+}
+
+/// The profiles that always exist, even with an empty config file
+fn builtin_profile(name: &str) -> Option<Profile> {
+    let base_url = match name {
+        DEFAULT_PROFILE => PROD_BASE_URL,
+        DEV_PROFILE => DEV_BASE_URL,
+        _ => return None,
+    };
+    Some(Profile {
+        base_url: Some(base_url.to_string()),
+        ..Profile::default()
+    })
+}
+
+/// Where the auth token for this invocation came from
+#[derive(Clone, PartialEq)]
+pub enum TokenSource {
+    Flag,
+    Env,
+    File(PathBuf),
+    Config,
+    Keyring,
+    None,
+}
+
+impl fmt::Display for TokenSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenSource::Flag => write!(f, "--token flag"),
+            TokenSource::Env => write!(f, "{} environment variable", TOKEN_ENV_VAR),
+            TokenSource::File(path) => write!(f, "token file {}", path.display()),
+            TokenSource::Config => write!(f, "saved profile"),
+            TokenSource::Keyring => write!(f, "OS keyring"),
+            TokenSource::None => write!(f, "none"),
+        }
+    }
+}
+
+/// Options given on the command line, checked before the environment and saved profile
+#[derive(Default)]
+pub struct ProfileOverrides {
+    pub token: Option<String>,
+    pub token_file: Option<PathBuf>,
+    pub base_url: Option<String>,
+    /// Stork region to use instead of the one in the base URL, e.g. `jp`
+    pub region: Option<String>,
+}
+
+/// A profile with built-in defaults applied, ready to be used for requests
+pub struct ActiveProfile {
+    pub name: String,
+    pub auth_token: Option<String>,
+    pub token_source: TokenSource,
+    pub base_url: String,
+    pub fallback_period_sec: Option<u64>,
+    pub percent_change_threshold: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct AuthConfig {
+    /// Single token written by older versions, migrated into the default profile on load
+    #[serde(default, skip_serializing)]
+    auth_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl AuthConfig {
+    /// Location of the auth config file, e.g. `~/.config/stork-asset/auth.yaml` on Linux
+    pub fn path() -> Result<PathBuf, String> {
+        dirs::config_dir()
+            .map(|dir| dir.join(CONFIG_DIR_NAME).join(AUTH_FILE_NAME))
+            .ok_or_else(|| "Unable to determine config directory".to_string())
+    }
+
+    /// Load the auth config, returning an empty config if none has been saved yet
+    pub fn load() -> Result<Self, String> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut config: Self = serde_yaml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+        if let Some(token) = config.auth_token.take() {
+            let default = config.profiles.entry(DEFAULT_PROFILE.to_string()).or_default();
+            if default.auth_token.is_none() {
+                default.auth_token = Some(token);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Write the auth config, readable and writable by the current user only
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let contents = serde_yaml::to_string(self)
+            .map_err(|e| format!("Failed to serialize auth config: {}", e))?;
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut file = options
+            .open(&path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+        // The mode above only applies on creation, so tighten an existing file too
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+                .map_err(|e| format!("Failed to set permissions on {}: {}", path.display(), e))?;
+        }
+
+        file.write_all(contents.as_bytes())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Name of the profile to use: the requested one, else the active one, else "default"
+    pub fn profile_name(&self, requested: Option<&str>) -> String {
+        requested
+            .or(self.active_profile.as_deref())
+            .unwrap_or(DEFAULT_PROFILE)
+            .to_string()
+    }
+
+    /// All known profile names, built-in ones included
+    pub fn profile_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.profiles.keys().cloned().collect();
+        for builtin in [DEFAULT_PROFILE, DEV_PROFILE] {
+            if !self.profiles.contains_key(builtin) {
+                names.push(builtin.to_string());
+            }
+        }
+        names.sort();
+        names
+    }
+
+    /// Look up a profile by name, layering saved settings over the built-in defaults
+    pub fn profile(&self, name: &str) -> Option<Profile> {
+        match (builtin_profile(name), self.profiles.get(name)) {
+            (Some(builtin), Some(saved)) => Some(builtin.merged_with(saved)),
+            (Some(builtin), None) => Some(builtin),
+            (None, Some(saved)) => Some(saved.clone()),
+            (None, None) => None,
+        }
+    }
+
+    /// Resolve the profile to use for this invocation.
+    ///
+    /// The auth token is taken from the first of: `--token`, `STORK_AUTH_TOKEN`, `--token-file`,
+    /// the token saved in the profile, and the token stored in the OS keyring for the profile.
+    pub fn resolve(&self, requested: Option<&str>, options: &ProfileOverrides) -> Result<ActiveProfile, String> {
+        let name = self.profile_name(requested);
+        let profile = self.profile(&name).ok_or_else(|| {
+            format!("Unknown profile '{}'. Run `stork-asset profile list` to see available profiles", name)
+        })?;
+
+        let base_url = base_url(&profile, options)?;
+        let env_token = env::var(TOKEN_ENV_VAR).ok().filter(|t| !t.trim().is_empty());
+        let (auth_token, token_source) = if let Some(token) = &options.token {
+            (Some(token.trim().to_string()), TokenSource::Flag)
+        } else if let Some(token) = env_token {
+            (Some(token.trim().to_string()), TokenSource::Env)
+        } else if let Some(path) = &options.token_file {
+            (Some(read_token_file(path)?), TokenSource::File(path.clone()))
+        } else if let Some(token) = profile.auth_token {
+            (Some(token), TokenSource::Config)
+        } else if let Some(token) = keyring_token(&name) {
+            (Some(token), TokenSource::Keyring)
+        } else {
+            (None, TokenSource::None)
+        };
+
+        Ok(ActiveProfile {
+            auth_token,
+            token_source,
+            base_url,
+            fallback_period_sec: profile.fallback_period_sec,
+            percent_change_threshold: profile.percent_change_threshold,
+            name,
+        })
+    }
+
+    /// REST API base URL for this invocation, without looking up the auth token
+    pub fn resolve_base_url(&self, requested: Option<&str>, options: &ProfileOverrides) -> Result<String, String> {
+        let name = self.profile_name(requested);
+        let profile = self.profile(&name).ok_or_else(|| format!("Unknown profile '{}'", name))?;
+        base_url(&profile, options)
+    }
+}
+
+/// The first of `--base-url`, `STORK_REST_URL` and the profile's base URL, with `--region` applied
+fn base_url(profile: &Profile, options: &ProfileOverrides) -> Result<String, String> {
+    let env_base_url = env::var(BASE_URL_ENV_VAR).ok().filter(|url| !url.trim().is_empty());
+    let base_url = options.base_url.clone()
+        .or(env_base_url)
+        .or(profile.base_url.clone())
+        .unwrap_or_else(|| PROD_BASE_URL.to_string())
+        .trim()
+        .trim_end_matches('/')
+        .to_string();
+    match &options.region {
+        Some(region) => with_region(&base_url, region),
+        None => Ok(base_url),
+    }
+}
+
+/// Point a Stork-hosted REST URL at another region, keeping the environment, e.g.
+/// `https://rest.dev.jp.stork-oracle.network` with region `us` becomes `https://rest.dev.us.stork-oracle.network`
+pub fn with_region(base_url: &str, region: &str) -> Result<String, String> {
+    let region = region.trim().to_lowercase();
+    if region.is_empty() || !region.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("Invalid region '{}'", region));
+    }
+    let (scheme, host) = base_url.split_once("://").unwrap_or(("https", base_url));
+    let mut labels: Vec<&str> = host.split('.').collect();
+    let is_stork_host = labels.len() >= 4
+        && labels[0] == "rest"
+        && labels[labels.len() - 2..] == ["stork-oracle", "network"];
+    if !is_stork_host {
+        return Err(format!("--region only applies to Stork-hosted REST URLs, not {}", base_url));
+    }
+    let region_index = labels.len() - 3;
+    labels[region_index] = &region;
+    Ok(format!("{}://{}", scheme, labels.join(".")))
+}
+
+/// Load the auth config and resolve the profile selected by `--profile`/`--dev`
+pub fn active_profile(requested: Option<&str>, options: &ProfileOverrides) -> Result<ActiveProfile, String> {
+    AuthConfig::load()?.resolve(requested, options)
+}
+
+fn read_token_file(path: &Path) -> Result<String, String> {
+    let token = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read token file {}: {}", path.display(), e))?;
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(format!("Token file {} is empty", path.display()));
+    }
+    Ok(token.to_string())
+}
+
+fn keyring_entry(profile: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, profile)
+        .map_err(|e| format!("Unable to access OS keyring: {}", e))
+}
+
+/// Token stored in the OS keyring for a profile. A missing or unavailable keyring counts as no token.
+pub fn keyring_token(profile: &str) -> Option<String> {
+    keyring_entry(profile).ok()?.get_password().ok()
+}
+
+/// Store a profile's token in the OS keyring
+pub fn set_keyring_token(profile: &str, token: &str) -> Result<(), String> {
+    keyring_entry(profile)?
+        .set_password(token)
+        .map_err(|e| format!("Failed to store token in OS keyring: {}", e))
+}
+
+/// Remove a profile's token from the OS keyring, returning whether there was one. Without a usable
+/// keyring, e.g. no Secret Service on headless Linux, there can't be one, so that isn't an error.
+pub fn delete_keyring_token(profile: &str) -> Result<bool, String> {
+    match keyring::Entry::new(KEYRING_SERVICE, profile).and_then(|entry| entry.delete_credential()) {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry | keyring::Error::PlatformFailure(_) | keyring::Error::NoStorageAccess(_)) => Ok(false),
+        Err(e) => Err(format!("Failed to remove token from OS keyring: {}", e)),
+    }
+}
@@ -0,0 +1,143 @@
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use stork_asset::http::HttpError;
+use stork_asset::StorkClient;
+
+use crate::auth::CONFIG_DIR_NAME;
+
+/// How the cached asset list may be used
+pub struct CacheOptions {
+    /// Maximum age of a cached list before it is fetched again
+    pub ttl: Duration,
+    /// Always fetch, ignoring any cached list
+    pub refresh: bool,
+    /// Never fetch, using the cached list whatever its age
+    pub offline: bool,
+}
+
+/// Asset list fetched from one REST API, as stored on disk
+#[derive(Serialize, Deserialize)]
+struct CachedAssets {
+    base_url: String,
+    /// Unix time in seconds
+    fetched_at: u64,
+    assets: Vec<String>,
+}
+
+impl CachedAssets {
+    fn age(&self) -> Duration {
+        Duration::from_secs(now().saturating_sub(self.fetched_at))
+    }
+}
+
+/// Current Unix time in seconds
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+/// Directory holding the cached asset lists
+pub fn cache_dir() -> Result<PathBuf, String> {
+    dirs::cache_dir()
+        .map(|dir| dir.join(CONFIG_DIR_NAME))
+        .ok_or_else(|| "Unable to determine cache directory".to_string())
+}
+
+/// A REST base URL as a file name, e.g. `rest.jp.stork-oracle.network` or `127.0.0.1_8080`
+pub fn url_slug(base_url: &str) -> String {
+    base_url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/')
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect()
+}
+
+/// One file per REST API, so profiles pointing at different environments don't mix
+fn cache_path(base_url: &str) -> Result<PathBuf, String> {
+    Ok(cache_dir()?.join(format!("assets-{}.json", url_slug(base_url))))
+}
+
+fn load(base_url: &str) -> Option<CachedAssets> {
+    let contents = fs::read_to_string(cache_path(base_url).ok()?).ok()?;
+    serde_json::from_str::<CachedAssets>(&contents)
+        .ok()
+        .filter(|cached| cached.base_url == base_url)
+}
+
+fn save(base_url: &str, assets: &[String]) -> Result<(), String> {
+    let path = cache_path(base_url)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let cached = CachedAssets { base_url: base_url.to_string(), fetched_at: now(), assets: assets.to_vec() };
+    let contents = serde_json::to_string(&cached)
+        .map_err(|e| format!("Failed to serialize asset cache: {}", e))?;
+    fs::write(&path, contents)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Get the available assets, from the cache while it is fresh and from the REST API otherwise.
+/// With `allow_stale`, an expired cached list is used if the REST API can't be reached.
+pub fn available_assets(client: &StorkClient, options: &CacheOptions, allow_stale: bool) -> Result<Vec<String>, HttpError> {
+    let base_url = client.base_url();
+    let cached = load(base_url);
+
+    if options.offline {
+        return cached
+            .map(|cached| cached.assets)
+            .ok_or_else(|| HttpError::Offline(format!("No cached asset list for {} (run once without --offline to fetch it)", base_url)));
+    }
+    if let Some(cached) = cached.as_ref().filter(|cached| !options.refresh && cached.age() <= options.ttl) {
+        return Ok(cached.assets.clone());
+    }
+
+    match client.available_assets() {
+        Ok(assets) => {
+            if let Err(e) = save(base_url, &assets) {
+                eprintln!("Warning: Could not cache asset list: {}", e);
+            }
+            Ok(assets)
+        }
+        Err(e) => match cached {
+            Some(cached) if allow_stale && e.is_unreachable() => {
+                eprintln!(
+                    "Warning: {}. Using the cached asset list from {} ago.",
+                    e,
+                    humantime::format_duration(Duration::from_secs(cached.age().as_secs() / 60 * 60)),
+                );
+                Ok(cached.assets)
+            }
+            _ => Err(e),
+        },
+    }
+}
+
+/// The cached asset list for a REST API, whatever its age, without ever fetching it
+pub fn cached_assets(base_url: &str) -> Option<Vec<String>> {
+    load(base_url).map(|cached| cached.assets)
+}
+
+/// Delete all cached asset lists, returning how many were removed
+pub fn clear() -> Result<usize, String> {
+    let dir = cache_dir()?;
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    for entry in fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))? {
+        let path = entry.map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?.path();
+        let is_asset_list = path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("assets-") && name.ends_with(".json"));
+        if is_asset_list {
+            fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,84 @@
+//! Blocking client for the Stork REST API.
+
+use reqwest::blocking::Client;
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::http::{self, HttpError, HttpSettings};
+use crate::prices::PriceUpdate;
+
+/// Production REST API
+pub const PROD_BASE_URL: &str = "https://rest.jp.stork-oracle.network";
+/// Development REST API
+pub const DEV_BASE_URL: &str = "https://rest.dev.jp.stork-oracle.network";
+
+#[derive(Deserialize)]
+struct AssetsResponse {
+    data: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct LatestPricesResponse {
+    data: BTreeMap<String, PriceUpdate>,
+}
+
+/// Blocking client for the Stork REST API
+///
+/// ```no_run
+/// use stork_asset::StorkClient;
+///
+/// let client = StorkClient::new("https://rest.jp.stork-oracle.network", Some("my-token"))?;
+/// let assets = client.available_assets()?;
+/// # Ok::<(), stork_asset::http::HttpError>(())
+/// ```
+pub struct StorkClient {
+    base_url: String,
+    token: Option<String>,
+    client: Client,
+    settings: HttpSettings,
+}
+
+impl StorkClient {
+    /// Client with the default timeouts and retries
+    pub fn new(base_url: &str, token: Option<&str>) -> Result<Self, HttpError> {
+        Self::with_settings(base_url, token, HttpSettings::default())
+    }
+
+    /// Client with custom timeouts, retries, proxy or CA certificates
+    pub fn with_settings(base_url: &str, token: Option<&str>, settings: HttpSettings) -> Result<Self, HttpError> {
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: token.map(str::to_string),
+            client: http::client(&settings)?,
+            settings,
+        })
+    }
+
+    /// REST API base URL, without a trailing slash
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// All asset IDs the REST API serves prices for
+    pub fn available_assets(&self) -> Result<Vec<String>, HttpError> {
+        let request = http::rest_get(&self.client, &self.base_url, "/v1/prices/assets", self.token.as_deref());
+        http::get_json::<AssetsResponse>(request, &self.settings).map(|response| response.data)
+    }
+
+    /// Latest prices for the given assets, keyed by asset ID. Assets without a price are left out.
+    pub fn latest_prices(&self, assets: &[String]) -> Result<BTreeMap<String, PriceUpdate>, HttpError> {
+        let request = http::rest_get(&self.client, &self.base_url, "/v1/prices/latest", self.token.as_deref())
+            .query(&[("assets", assets.join(","))]);
+        http::get_json::<LatestPricesResponse>(request, &self.settings).map(|response| response.data)
+    }
+
+    /// Check whether the REST API accepts the auth token. Returns `Ok(false)` if it is rejected.
+    pub fn check_auth(&self) -> Result<bool, HttpError> {
+        let request = http::rest_get(&self.client, &self.base_url, "/v1/prices/assets", self.token.as_deref());
+        match http::send(request, &self.settings) {
+            Ok(_) => Ok(true),
+            Err(HttpError::Auth(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
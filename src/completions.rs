@@ -0,0 +1,113 @@
+use clap::Command;
+use clap_complete::env::Shells;
+use clap_complete::{CompletionCandidate, Shell};
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::auth::{AuthConfig, ProfileOverrides, DEV_PROFILE};
+use crate::cache;
+
+/// Environment variable the shell sets when asking `stork-asset` for completions
+pub const COMPLETE_ENV_VAR: &str = "COMPLETE";
+
+const BIN_NAME: &str = "stork-asset";
+
+/// Write the script registering completions for `shell`. The script calls back into
+/// `stork-asset` on each completion, so it always matches the installed version.
+pub fn write_registration(shell: Shell, out: &mut dyn Write) -> io::Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&shell.to_string())
+        .ok_or_else(|| io::Error::other(format!("Completions are not supported for {}", shell)))?;
+    completer.write_registration(COMPLETE_ENV_VAR, BIN_NAME, BIN_NAME, BIN_NAME, out)
+}
+
+/// Complete asset IDs from the cached asset list of the profile being used. Handles comma-separated lists
+/// by completing the last entry, and never contacts the REST API so completion stays instant.
+pub fn asset_ids(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return Vec::new();
+    };
+    let (done, partial) = match current.rsplit_once(',') {
+        Some((done, partial)) => (format!("{},", done), partial),
+        None => (String::new(), current),
+    };
+    // Nothing to offer once per-asset settings (ID:FALLBACK:PERCENT) are being typed
+    if partial.contains(':') {
+        return Vec::new();
+    }
+    let Some(assets) = cached_assets() else {
+        return Vec::new();
+    };
+
+    let listed: Vec<String> = done.split(',')
+        .map(|spec| spec.split(':').next().unwrap_or_default().trim().to_uppercase())
+        .collect();
+    let partial = partial.to_uppercase();
+    let mut candidates: Vec<&String> = assets.iter()
+        .filter(|asset| {
+            let asset = asset.to_uppercase();
+            asset.starts_with(&partial) && !listed.contains(&asset)
+        })
+        .collect();
+    candidates.sort();
+    candidates.into_iter()
+        .map(|asset| CompletionCandidate::new(format!("{}{}", done, asset)))
+        .collect()
+}
+
+/// Cached asset list for the profile the command line being completed would use
+fn cached_assets() -> Option<Vec<String>> {
+    let (profile, overrides) = command_line_profile();
+    let base_url = AuthConfig::load().ok()?
+        .resolve_base_url(profile.as_deref(), &overrides)
+        .ok()?;
+    cache::cached_assets(&base_url)
+}
+
+/// `--profile`/`--dev`/`--base-url`/`--region` from the words on the command line being completed,
+/// which the shell passes as `stork-asset -- <words>`
+fn command_line_profile() -> (Option<String>, ProfileOverrides) {
+    let words: Vec<String> = env::args_os()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .skip_while(|arg| arg != "--")
+        .skip(1)
+        .collect();
+
+    let mut profile = None;
+    let mut overrides = ProfileOverrides::default();
+    let mut words = words.into_iter();
+    while let Some(word) = words.next() {
+        let (flag, inline_value) = match word.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (word, None),
+        };
+        let mut value = || inline_value.clone().or_else(|| words.next());
+        match flag.as_str() {
+            "--dev" => profile = Some(DEV_PROFILE.to_string()),
+            "--profile" => profile = value(),
+            "--base-url" => overrides.base_url = value(),
+            "--region" => overrides.region = value(),
+            _ => {}
+        }
+    }
+    (profile, overrides)
+}
+
+/// Write the man page for `command` to stdout, or one page per command and subcommand to `dir`
+pub fn write_man_pages(command: Command, dir: Option<&Path>) -> Result<(), String> {
+    match dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+            clap_mangen::generate_to(command, dir)
+                .map_err(|e| format!("Failed to write man pages to {}: {}", dir.display(), e))
+        }
+        None => clap_mangen::Man::new(command)
+            .render(&mut io::stdout().lock())
+            .map_err(|e| format!("Failed to write man page: {}", e)),
+    }
+}
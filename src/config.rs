@@ -0,0 +1,439 @@
+//! Asset config files, and the rules that set each asset's fallback period and percent change
+//! threshold.
+
+use glob::{MatchOptions, Pattern};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::encode::calculate_encoded_asset_id;
+
+/// Fallback period for assets no rule or option sets one for
+pub const DEFAULT_FALLBACK_PERIOD_SEC: u64 = 60;
+/// Percent change threshold for assets no rule or option sets one for
+pub const DEFAULT_PERCENT_CHANGE: f64 = 1.0;
+
+/// One asset's entry in an asset config file
+#[derive(Serialize, Deserialize)]
+pub struct AssetConfig {
+    /// Stork asset ID, e.g. `BTCUSD`
+    pub asset_id: String,
+    /// Seconds after which a price is pushed even if it hasn't moved
+    pub fallback_period_sec: u64,
+    /// Percent move that triggers a push before the fallback period
+    pub percent_change_threshold: f64,
+    /// Keccak-256 hash of `asset_id`, as used on chain
+    pub encoded_asset_id: String,
+}
+
+/// An asset config file, keyed by asset ID
+#[derive(Serialize, Deserialize, Default)]
+pub struct Config {
+    /// Assets by key, normally their asset ID
+    pub assets: BTreeMap<String, AssetConfig>,
+    /// The `#` comment lines at the top of the file, written back when the config is saved
+    #[serde(skip)]
+    pub header: Vec<String>,
+}
+
+impl Config {
+    /// Load an asset config YAML file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut config: Self = serde_yaml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+        config.header = leading_comments(&contents);
+        Ok(config)
+    }
+
+    /// Append lines to the header, as `#` comments
+    pub fn add_header(&mut self, lines: &[String]) {
+        self.header.extend(lines.iter().map(|line| format!("# {}", line)));
+    }
+
+    /// Write the config as YAML, preceded by its header
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let yaml_content = serde_yaml::to_string(self)
+            .map_err(|e| format!("Failed to serialize config: {}", e))?;
+        let mut content: String = self.header.iter()
+            .map(|line| format!("{}\n", line))
+            .collect();
+        content.push_str(&yaml_content);
+        fs::write(path, content)
+            .map_err(|e| format!("Error writing file: {}", e))
+    }
+
+    /// Add or update assets. New assets get their settings from `rules`; assets already in the
+    /// config keep their existing values apart from any given inline (`-a ID:FALLBACK:PERCENT`).
+    /// Returns the number of assets added and updated.
+    pub fn merge_assets(&mut self, asset_ids: &[String], rules: &OverrideRules) -> (usize, usize) {
+        let (mut added, mut updated) = (0, 0);
+        for asset_id in asset_ids {
+            match self.assets.get_mut(asset_id) {
+                Some(existing) => {
+                    if let Some(settings) = rules.inline_settings(asset_id) {
+                        if let Some(fallback) = settings.fallback_period_sec {
+                            existing.fallback_period_sec = fallback;
+                        }
+                        if let Some(percent) = settings.percent_change_threshold {
+                            existing.percent_change_threshold = percent;
+                        }
+                    }
+                    updated += 1;
+                }
+                None => {
+                    self.assets.insert(asset_id.clone(), rules.asset_config(asset_id));
+                    added += 1;
+                }
+            }
+        }
+        self.recompute_encoded_ids();
+        (added, updated)
+    }
+
+    /// Remove assets, returning the IDs that were not in the config
+    pub fn remove_assets(&mut self, asset_ids: &[String]) -> Vec<String> {
+        let missing = asset_ids.iter()
+            .filter(|asset_id| self.assets.remove(*asset_id).is_none())
+            .cloned()
+            .collect();
+        self.recompute_encoded_ids();
+        missing
+    }
+
+    /// Recompute every encoded asset ID from its asset ID
+    pub fn recompute_encoded_ids(&mut self) {
+        for asset in self.assets.values_mut() {
+            asset.encoded_asset_id = calculate_encoded_asset_id(&asset.asset_id);
+        }
+    }
+}
+
+/// The block of `#` comment lines at the top of a YAML file, up to the first other line
+fn leading_comments(contents: &str) -> Vec<String> {
+    let mut lines: Vec<String> = contents.lines()
+        .take_while(|line| line.trim().is_empty() || line.trim_start().starts_with('#'))
+        .map(|line| line.trim_end().to_string())
+        .collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+/// Fallback period and/or percent change threshold for one asset or a group of assets
+#[derive(Deserialize, Default, Clone, Copy)]
+pub struct AssetSettings {
+    /// Seconds after which a price is pushed even if it hasn't moved
+    #[serde(default)]
+    pub fallback_period_sec: Option<u64>,
+    /// Percent move that triggers a push before the fallback period
+    #[serde(default)]
+    pub percent_change_threshold: Option<f64>,
+}
+
+impl AssetSettings {
+    /// Parse the `FALLBACK:PERCENT` part of an asset spec. Either value may be left empty.
+    fn parse(fallback: Option<&str>, percent: Option<&str>, spec: &str) -> Result<Self, String> {
+        let fallback_period_sec = match fallback.map(str::trim).filter(|s| !s.is_empty()) {
+            Some(value) => Some(value.parse::<u64>()
+                .map_err(|_| format!("Invalid fallback period '{}' in '{}'", value, spec))?),
+            None => None,
+        };
+        let percent_change_threshold = match percent.map(str::trim).filter(|s| !s.is_empty()) {
+            Some(value) => Some(value.parse::<f64>()
+                .map_err(|_| format!("Invalid percent change threshold '{}' in '{}'", value, spec))?),
+            None => None,
+        };
+        Ok(Self { fallback_period_sec, percent_change_threshold })
+    }
+}
+
+/// Parse a single `ID[:FALLBACK[:PERCENT]]` spec, e.g. `BTCUSD:30:0.1`, `ETHUSD::0.5` or `*USDT:120`
+pub fn parse_asset_spec(spec: &str) -> Result<(String, AssetSettings), String> {
+    let mut parts = spec.trim().split(':');
+    let id = parts.next().unwrap_or_default().trim().to_string();
+    if id.is_empty() {
+        return Err(format!("Missing asset ID in '{}'", spec));
+    }
+    let settings = AssetSettings::parse(parts.next(), parts.next(), spec)?;
+    if parts.next().is_some() {
+        return Err(format!("Too many fields in '{}', expected ID:FALLBACK:PERCENT", spec));
+    }
+    Ok((id, settings))
+}
+
+/// Parse a comma-separated list of asset specs
+pub fn parse_asset_specs(list: &str) -> Result<Vec<(String, AssetSettings)>, String> {
+    list.split(',')
+        .filter(|spec| !spec.trim().is_empty())
+        .map(parse_asset_spec)
+        .collect()
+}
+
+fn is_glob(key: &str) -> bool {
+    key.contains(['*', '?', '['])
+}
+
+struct GlobRule {
+    pattern: Pattern,
+    settings: AssetSettings,
+}
+
+impl GlobRule {
+    fn new(pattern: &str, settings: AssetSettings) -> Result<Self, String> {
+        let pattern = Pattern::new(pattern)
+            .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?;
+        Ok(Self { pattern, settings })
+    }
+
+    /// Number of literal characters; patterns with more of them are more specific
+    fn specificity(&self) -> usize {
+        self.pattern.as_str().chars().filter(|c| !matches!(c, '*' | '?' | '[' | ']')).count()
+    }
+
+    fn matches(&self, asset_id: &str) -> bool {
+        let options = MatchOptions { case_sensitive: false, ..MatchOptions::new() };
+        self.pattern.matches_with(asset_id, options)
+    }
+}
+
+/// Decides the fallback period and threshold for each asset in a generated config.
+///
+/// Each value is resolved independently, taking the first of:
+/// 1. the asset's inline spec (`-a BTCUSD:30:0.1`)
+/// 2. the asset's exact entry in the overrides file or a non-pattern `--rule`
+/// 3. the most specific matching glob rule (`--rule` or a pattern key in the overrides file);
+///    ties go to `--rule` over the file, and to later rules over earlier ones
+/// 4. `-f` / `-p`
+/// 5. the profile's defaults
+/// 6. 60 seconds and 1.0 percent
+#[derive(Default)]
+pub struct OverrideRules {
+    inline: BTreeMap<String, AssetSettings>,
+    exact: BTreeMap<String, AssetSettings>,
+    globs: Vec<GlobRule>,
+    defaults: AssetSettings,
+}
+
+impl OverrideRules {
+    /// Settings used when nothing more specific applies (`-f`/`-p` layered over the profile)
+    pub fn with_defaults(defaults: AssetSettings) -> Self {
+        Self { defaults, ..Self::default() }
+    }
+
+    /// Add settings given inline with `-a`
+    pub fn add_inline(&mut self, asset_id: &str, settings: AssetSettings) {
+        self.inline.insert(asset_id.to_uppercase(), settings);
+    }
+
+    /// Settings given inline with `-a` for an asset, if any
+    pub fn inline_settings(&self, asset_id: &str) -> Option<&AssetSettings> {
+        self.inline.get(&asset_id.to_uppercase())
+    }
+
+    /// Add a `--rule PATTERN[:FALLBACK[:PERCENT]]`
+    pub fn add_rule(&mut self, spec: &str) -> Result<(), String> {
+        let (pattern, settings) = parse_asset_spec(spec)?;
+        if is_glob(&pattern) {
+            self.globs.push(GlobRule::new(&pattern, settings)?);
+        } else {
+            self.exact.insert(pattern.to_uppercase(), settings);
+        }
+        Ok(())
+    }
+
+    /// Load an overrides file mapping asset IDs or glob patterns to settings, e.g.
+    ///
+    /// ```yaml
+    /// BTCUSD:
+    ///   fallback_period_sec: 30
+    /// "*USDT":
+    ///   percent_change_threshold: 0.5
+    /// ```
+    ///
+    /// File rules are added before any `--rule` flags so those take precedence on ties.
+    pub fn load_file(&mut self, path: &Path) -> Result<(), String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        // A mapping keeps the file's order, which breaks ties between equally specific patterns
+        let entries: serde_yaml::Mapping = serde_yaml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+        let mut globs = Vec::new();
+        for (key, value) in entries {
+            let key = key.as_str()
+                .ok_or_else(|| format!("Invalid key in {}: asset IDs and patterns must be strings", path.display()))?
+                .to_string();
+            let settings: AssetSettings = serde_yaml::from_value(value)
+                .map_err(|e| format!("Invalid settings for '{}' in {}: {}", key, path.display(), e))?;
+            if is_glob(&key) {
+                globs.push(GlobRule::new(&key, settings)?);
+            } else {
+                self.exact.entry(key.to_uppercase()).or_insert(settings);
+            }
+        }
+        self.globs.splice(0..0, globs);
+        Ok(())
+    }
+
+    fn best_glob<T>(&self, asset_id: &str, field: impl Fn(&AssetSettings) -> Option<T>) -> Option<T> {
+        self.globs.iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.matches(asset_id))
+            .filter_map(|(index, rule)| field(&rule.settings).map(|value| (rule.specificity(), index, value)))
+            .max_by_key(|(specificity, index, _)| (*specificity, *index))
+            .map(|(_, _, value)| value)
+    }
+
+    fn lookup<T>(&self, asset_id: &str, field: impl Fn(&AssetSettings) -> Option<T>) -> Option<T> {
+        let key = asset_id.to_uppercase();
+        self.inline.get(&key).and_then(&field)
+            .or_else(|| self.exact.get(&key).and_then(&field))
+            .or_else(|| self.best_glob(&key, &field))
+            .or_else(|| field(&self.defaults))
+    }
+
+    /// Fallback period for an asset, from the most specific rule that sets one
+    pub fn fallback_period(&self, asset_id: &str) -> u64 {
+        self.lookup(asset_id, |settings| settings.fallback_period_sec)
+            .unwrap_or(DEFAULT_FALLBACK_PERIOD_SEC)
+    }
+
+    /// Percent change threshold for an asset, from the most specific rule that sets one
+    pub fn percent_change(&self, asset_id: &str) -> f64 {
+        self.lookup(asset_id, |settings| settings.percent_change_threshold)
+            .unwrap_or(DEFAULT_PERCENT_CHANGE)
+    }
+
+    /// Build the config entry for an asset
+    pub fn asset_config(&self, asset_id: &str) -> AssetConfig {
+        AssetConfig {
+            asset_id: asset_id.to_string(),
+            fallback_period_sec: self.fallback_period(asset_id),
+            percent_change_threshold: self.percent_change(asset_id),
+            encoded_asset_id: calculate_encoded_asset_id(asset_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leading_comment_block_is_kept() {
+        let contents = "# Generated by stork-asset 0.2.1\n# Random selection: -r 5 --seed 1\n\n#  hand-tuned\n\nassets:\n  # not kept\n  BTCUSD: {}\n";
+        assert_eq!(leading_comments(contents), vec![
+            "# Generated by stork-asset 0.2.1",
+            "# Random selection: -r 5 --seed 1",
+            "",
+            "#  hand-tuned",
+        ]);
+        assert!(leading_comments("assets: {}\n# trailing\n").is_empty());
+    }
+
+    #[test]
+    fn header_survives_load_and_save() {
+        let path = std::env::temp_dir().join(format!("stork-asset-config-test-{}.yaml", std::process::id()));
+        let mut config = Config::default();
+        config.add_header(&["Generated by stork-asset".to_string()]);
+        config.merge_assets(&["BTCUSD".to_string()], &OverrideRules::default());
+        config.save(&path).unwrap();
+
+        let mut config = Config::load(&path).unwrap();
+        config.add_header(&["Random selection: -r 1 --seed 2".to_string()]);
+        config.merge_assets(&["ETHUSD".to_string()], &OverrideRules::default());
+        config.save(&path).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let assets = Config::load(&path).unwrap().assets.len();
+        fs::remove_file(&path).unwrap();
+        assert!(contents.starts_with("# Generated by stork-asset\n# Random selection: -r 1 --seed 2\nassets:\n"), "{}", contents);
+        assert_eq!(assets, 2);
+    }
+
+    fn settings(fallback: Option<u64>, percent: Option<f64>) -> AssetSettings {
+        AssetSettings { fallback_period_sec: fallback, percent_change_threshold: percent }
+    }
+
+    /// Rules from `--rule` flags, after an overrides file with the given contents
+    fn rules_with_file(file: &str, rules: &[&str]) -> OverrideRules {
+        let path = std::env::temp_dir().join(format!("stork-asset-overrides-test-{}.yaml", std::process::id()));
+        fs::write(&path, file).unwrap();
+        let mut override_rules = OverrideRules::default();
+        let loaded = override_rules.load_file(&path);
+        fs::remove_file(&path).unwrap();
+        loaded.unwrap();
+        for rule in rules {
+            override_rules.add_rule(rule).unwrap();
+        }
+        override_rules
+    }
+
+    #[test]
+    fn inline_beats_exact() {
+        let mut rules = OverrideRules::default();
+        rules.add_rule("BTCUSD:30:0.5").unwrap();
+        rules.add_inline("btcusd", settings(Some(10), None));
+        assert_eq!(rules.fallback_period("BTCUSD"), 10);
+        // Values the inline spec leaves out still come from the exact rule
+        assert_eq!(rules.percent_change("BTCUSD"), 0.5);
+    }
+
+    #[test]
+    fn exact_beats_most_specific_glob() {
+        let mut rules = OverrideRules::default();
+        rules.add_rule("BTCUSD:30").unwrap();
+        rules.add_rule("BTCUS?:20").unwrap();
+        assert_eq!(rules.fallback_period("btcusd"), 30);
+    }
+
+    #[test]
+    fn most_specific_glob_wins() {
+        let mut rules = OverrideRules::default();
+        rules.add_rule("BTC*:20").unwrap();
+        rules.add_rule("*:90").unwrap();
+        assert_eq!(rules.fallback_period("BTCUSDT"), 20);
+        assert_eq!(rules.fallback_period("ETHUSDT"), 90);
+    }
+
+    #[test]
+    fn later_rule_wins_tie() {
+        let mut rules = OverrideRules::default();
+        rules.add_rule("*USDT:20").unwrap();
+        rules.add_rule("BTCU*:40").unwrap();
+        assert_eq!(rules.fallback_period("BTCUSDT"), 40);
+        rules.add_rule("*USDT:60").unwrap();
+        assert_eq!(rules.fallback_period("BTCUSDT"), 60);
+    }
+
+    #[test]
+    fn rule_beats_file_on_tie() {
+        let rules = rules_with_file("\"*USDT\":\n  fallback_period_sec: 20\nBTCUSD:\n  fallback_period_sec: 25\n", &["BTCU*:40", "BTCUSD:45"]);
+        assert_eq!(rules.fallback_period("BTCUSDT"), 40);
+        assert_eq!(rules.fallback_period("BTCUSD"), 45);
+        // A more specific file pattern still beats a less specific rule
+        let rules = rules_with_file("\"BTCUSD?\":\n  fallback_period_sec: 20\n", &["BTC*:40"]);
+        assert_eq!(rules.fallback_period("BTCUSDT"), 20);
+    }
+
+    #[test]
+    fn defaults_apply_last() {
+        let rules = OverrideRules::with_defaults(settings(Some(120), None));
+        assert_eq!(rules.fallback_period("BTCUSD"), 120);
+        assert_eq!(rules.percent_change("BTCUSD"), DEFAULT_PERCENT_CHANGE);
+    }
+
+    #[test]
+    fn asset_spec_errors() {
+        for spec in ["BTCUSD:x", "BTCUSD::x", "A:1:2:3", ":30", ""] {
+            assert!(parse_asset_spec(spec).is_err(), "'{}' should be rejected", spec);
+        }
+        let (id, parsed) = parse_asset_spec(" BTCUSD::0.5 ").unwrap();
+        assert_eq!(id, "BTCUSD");
+        assert_eq!((parsed.fallback_period_sec, parsed.percent_change_threshold), (None, Some(0.5)));
+    }
+}
@@ -0,0 +1,149 @@
+//! Comparing two asset configs, or a config against the live asset list.
+
+use crate::config::{AssetConfig, Config};
+
+/// A field that differs between the two versions of an asset
+pub struct FieldChange {
+    /// Name of the field in the config file
+    pub field: &'static str,
+    /// Value in the old config
+    pub old: String,
+    /// Value in the new config
+    pub new: String,
+}
+
+/// An asset in both configs with different settings
+pub struct AssetChange {
+    /// The asset's key in the config
+    pub asset_id: String,
+    /// The fields that differ
+    pub changes: Vec<FieldChange>,
+}
+
+impl AssetChange {
+    /// The changes as one line, e.g. `fallback_period_sec 60 -> 30`
+    pub fn describe(&self) -> String {
+        self.changes.iter()
+            .map(|field| format!("{} {} -> {}", field.field, field.old, field.new))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Differences between two configs, or between a config and the live asset list
+#[derive(Default)]
+pub struct ConfigDiff {
+    /// Assets only in the new config
+    pub added: Vec<String>,
+    /// Assets only in the old config, or configured assets Stork no longer lists
+    pub removed: Vec<String>,
+    /// Assets in both with different settings
+    pub changed: Vec<AssetChange>,
+    /// Live assets not in the config. Only counted, since it is usually most of the live list.
+    pub unconfigured_live: Option<usize>,
+}
+
+impl ConfigDiff {
+    /// Whether nothing was added, removed or changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+fn field_changes(old: &AssetConfig, new: &AssetConfig) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    if old.fallback_period_sec != new.fallback_period_sec {
+        changes.push(FieldChange {
+            field: "fallback_period_sec",
+            old: old.fallback_period_sec.to_string(),
+            new: new.fallback_period_sec.to_string(),
+        });
+    }
+    if old.percent_change_threshold != new.percent_change_threshold {
+        changes.push(FieldChange {
+            field: "percent_change_threshold",
+            old: old.percent_change_threshold.to_string(),
+            new: new.percent_change_threshold.to_string(),
+        });
+    }
+    if old.encoded_asset_id != new.encoded_asset_id {
+        changes.push(FieldChange {
+            field: "encoded_asset_id",
+            old: old.encoded_asset_id.clone(),
+            new: new.encoded_asset_id.clone(),
+        });
+    }
+    changes
+}
+
+/// Compare two configs by map key
+pub fn diff_configs(old: &Config, new: &Config) -> ConfigDiff {
+    let mut diff = ConfigDiff::default();
+    for (key, old_asset) in &old.assets {
+        match new.assets.get(key) {
+            Some(new_asset) => {
+                let changes = field_changes(old_asset, new_asset);
+                if !changes.is_empty() {
+                    diff.changed.push(AssetChange { asset_id: key.clone(), changes });
+                }
+            }
+            None => diff.removed.push(key.clone()),
+        }
+    }
+    diff.added = new.assets.keys()
+        .filter(|key| !old.assets.contains_key(*key))
+        .cloned()
+        .collect();
+    diff
+}
+
+/// Compare a config against the live asset list. Configured assets Stork no longer lists are reported as removed.
+pub fn diff_live(config: &Config, live: &[String]) -> ConfigDiff {
+    ConfigDiff {
+        removed: config.assets.values()
+            .filter(|asset| !live.contains(&asset.asset_id))
+            .map(|asset| asset.asset_id.clone())
+            .collect(),
+        unconfigured_live: Some(live.iter()
+            .filter(|asset| !config.assets.values().any(|configured| &configured.asset_id == *asset))
+            .count()),
+        ..ConfigDiff::default()
+    }
+}
+
+/// Render the diff as JSON
+pub fn render_json(diff: &ConfigDiff) -> String {
+    let json_data = serde_json::json!({
+        "added": diff.added,
+        "removed": diff.removed,
+        "changed": diff.changed.iter()
+            .map(|change| serde_json::json!({
+                "asset_id": change.asset_id,
+                "changes": change.changes.iter()
+                    .map(|field| serde_json::json!({
+                        "field": field.field,
+                        "old": field.old,
+                        "new": field.new,
+                    }))
+                    .collect::<Vec<_>>()
+            }))
+            .collect::<Vec<_>>(),
+        "unconfigured_live": diff.unconfigured_live,
+    });
+    serde_json::to_string_pretty(&json_data).unwrap()
+}
+
+/// Render the diff as a Markdown table
+pub fn render_markdown(diff: &ConfigDiff, removed_label: &str) -> String {
+    let mut output = String::from("| Change | Asset ID | Details |\n|----------|----------|----------|\n");
+    for asset in &diff.added {
+        output.push_str(&format!("| added | {} | |\n", asset));
+    }
+    for asset in &diff.removed {
+        output.push_str(&format!("| {} | {} | |\n", removed_label, asset));
+    }
+    for change in &diff.changed {
+        output.push_str(&format!("| changed | {} | {} |\n", change.asset_id, change.describe()));
+    }
+    output
+}
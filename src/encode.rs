@@ -0,0 +1,32 @@
+//! Encoding asset IDs into the form used on chain, and decoding them back.
+
+use std::collections::BTreeMap;
+use tiny_keccak::{Hasher, Keccak};
+
+/// Encoded form of an asset ID as used on-chain: the Keccak-256 hash of the ID as `0x`-prefixed hex,
+/// e.g. `BTCUSD` -> `0x7404e3d1...`
+pub fn calculate_encoded_asset_id(asset_id: &str) -> String {
+    let mut keccak = Keccak::v256();
+    let mut output = [0u8; 32];
+    keccak.update(asset_id.as_bytes());
+    keccak.finalize(&mut output);
+    format!("0x{}", hex::encode(output))
+}
+
+/// Normalise an encoded asset ID to lowercase `0x`-prefixed hex, accepting it with or without
+/// the prefix. Fails unless it is exactly 32 bytes of hex.
+pub fn parse_encoded_asset_id(value: &str) -> Result<String, String> {
+    let trimmed = value.trim();
+    let digits = trimmed.strip_prefix("0x").or_else(|| trimmed.strip_prefix("0X")).unwrap_or(trimmed);
+    if digits.len() != 64 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid encoded asset ID '{}': expected 0x followed by 64 hex digits", trimmed));
+    }
+    Ok(format!("0x{}", digits.to_lowercase()))
+}
+
+/// Index assets by their encoded IDs, for looking up the asset behind an encoded ID
+pub fn encoded_asset_index(assets: &[String]) -> BTreeMap<String, String> {
+    assets.iter()
+        .map(|asset| (calculate_encoded_asset_id(asset), asset.clone()))
+        .collect()
+}
@@ -0,0 +1,68 @@
+use std::fmt::Display;
+use std::process;
+use stork_asset::http::HttpError;
+
+use crate::subscribe::SubscribeError;
+use crate::update;
+
+/// Process exit codes. Usage errors exit with 2, as reported by clap.
+#[derive(Clone, Copy)]
+pub enum ExitCode {
+    /// Any failure without a more specific code
+    Error = 1,
+    /// A requested or configured asset isn't listed by the REST API
+    Unavailable = 3,
+    /// An asset config file couldn't be parsed or failed validation
+    InvalidConfig = 4,
+    /// The REST API couldn't be reached or returned an unexpected response
+    Network = 5,
+    /// The auth token is missing or was rejected
+    Auth = 6,
+    /// An output file couldn't be written
+    FileWrite = 7,
+    /// `update --check` found a newer release
+    UpdateAvailable = 8,
+}
+
+impl ExitCode {
+    pub fn exit(self) -> ! {
+        update::finish_background_check();
+        process::exit(self as i32)
+    }
+}
+
+impl From<&HttpError> for ExitCode {
+    fn from(e: &HttpError) -> Self {
+        match e {
+            HttpError::Auth(_) => ExitCode::Auth,
+            HttpError::Config(_) => ExitCode::Error,
+            HttpError::Network(_)
+            | HttpError::RateLimited
+            | HttpError::Status(_)
+            | HttpError::Parse(_)
+            | HttpError::Offline(_) => ExitCode::Network,
+        }
+    }
+}
+
+impl From<&SubscribeError> for ExitCode {
+    fn from(e: &SubscribeError) -> Self {
+        match e {
+            SubscribeError::Invalid(_) => ExitCode::Error,
+            SubscribeError::Transport(_) => ExitCode::Network,
+            SubscribeError::Auth(_) => ExitCode::Auth,
+            SubscribeError::Rejected(_) => ExitCode::Unavailable,
+        }
+    }
+}
+
+/// Print an error to stderr and exit with `code`
+pub fn fail(code: ExitCode, message: impl Display) -> ! {
+    eprintln!("Error: {}", message);
+    code.exit()
+}
+
+/// Print a request error to stderr and exit with the matching code
+pub fn fail_http(e: HttpError) -> ! {
+    fail(ExitCode::from(&e), e)
+}
@@ -0,0 +1,160 @@
+//! Building an asset config from requested and randomly picked assets.
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::fmt;
+
+use crate::config::{Config, OverrideRules};
+use crate::selection::RandomFilter;
+
+/// Random assets to add to a generated config
+pub struct RandomSelection<'a> {
+    /// How many assets to pick
+    pub count: usize,
+    /// The same seed, asset list and filter always pick the same assets
+    pub seed: u64,
+    /// Narrows and weights the pool the assets are picked from
+    pub filter: &'a RandomFilter,
+}
+
+/// What [`generate`] changed
+pub struct Generated {
+    /// Assets that weren't in the config before
+    pub added: usize,
+    /// Assets that were already in the config
+    pub updated: usize,
+    /// How many assets the random ones were picked from, if any were requested
+    pub candidates: Option<usize>,
+}
+
+/// Ways [`generate`] can fail
+#[derive(Debug)]
+pub enum GenerateError {
+    /// A requested asset is neither in the config nor available
+    Unavailable(String),
+    /// No requested or random assets were given
+    NoAssets,
+}
+
+impl fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerateError::Unavailable(asset) => write!(f, "Asset '{}' not found in available assets", asset),
+            GenerateError::NoAssets => write!(f, "No assets selected"),
+        }
+    }
+}
+
+impl std::error::Error for GenerateError {}
+
+/// Add the `requested` assets and a random selection from `available` to `config`, with
+/// settings from `rules`.
+///
+/// The random assets are drawn from every available asset matching the filter, before the
+/// requested assets and those already in the config are taken out, so the seed and filter alone
+/// reproduce the same picks. Picks that are requested or already in the config aren't added twice.
+pub fn generate(
+    config: &mut Config,
+    available: &[String],
+    requested: &[String],
+    random: Option<&RandomSelection>,
+    rules: &OverrideRules,
+) -> Result<Generated, GenerateError> {
+    let mut selected_assets = Vec::new();
+
+    // First, add specifically requested assets
+    for asset in requested {
+        if !available.contains(asset) && !config.assets.contains_key(asset) {
+            return Err(GenerateError::Unavailable(asset.clone()));
+        }
+        if !selected_assets.contains(asset) {
+            selected_assets.push(asset.clone());
+        }
+    }
+
+    // Then add random assets if requested
+    let mut candidates = None;
+    if let Some(random) = random.filter(|random| random.count > 0) {
+        let mut pool: Vec<String> = available.iter()
+            .filter(|asset| random.filter.matches(asset))
+            .cloned()
+            .collect();
+        candidates = Some(pool.len());
+        // The server's ordering isn't guaranteed, so sort for a stable pool
+        pool.sort();
+        pool.dedup();
+        let mut rng = ChaCha8Rng::seed_from_u64(random.seed);
+        for asset in random.filter.choose(&pool, random.count, &mut rng) {
+            if !selected_assets.contains(&asset) && !config.assets.contains_key(&asset) {
+                selected_assets.push(asset);
+            }
+        }
+    }
+
+    if selected_assets.is_empty() {
+        return Err(GenerateError::NoAssets);
+    }
+
+    let (added, updated) = config.merge_assets(&selected_assets, rules);
+    Ok(Generated { added, updated, candidates })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn available() -> Vec<String> {
+        ["BTCUSD", "ETHUSD", "SOLUSD", "DOGEUSD", "LINKUSD", "XRPUSD", "ADAUSD", "AVAXUSD", "DOTUSD", "LTCUSD"]
+            .iter()
+            .map(|asset| asset.to_string())
+            .collect()
+    }
+
+    fn generated_ids(requested: &[&str], seed: u64, filter: &RandomFilter) -> Vec<String> {
+        let mut config = Config::default();
+        let requested: Vec<String> = requested.iter().map(|asset| asset.to_string()).collect();
+        let random = RandomSelection { count: 3, seed, filter };
+        generate(&mut config, &available(), &requested, Some(&random), &OverrideRules::default()).unwrap();
+        config.assets.into_keys().collect()
+    }
+
+    #[test]
+    fn same_seed_pool_and_requested_give_same_picks() {
+        let filter = RandomFilter::new(Some("USD"), None, None, true).unwrap();
+        let first = generated_ids(&["BTCUSD", "ETHUSD"], 42, &filter);
+        let mut shuffled = available();
+        shuffled.reverse();
+        let mut config = Config::default();
+        let random = RandomSelection { count: 3, seed: 42, filter: &filter };
+        let requested = vec!["BTCUSD".to_string(), "ETHUSD".to_string()];
+        generate(&mut config, &shuffled, &requested, Some(&random), &OverrideRules::default()).unwrap();
+        assert_eq!(first, config.assets.into_keys().collect::<Vec<_>>());
+        assert_eq!(first, generated_ids(&["BTCUSD", "ETHUSD"], 42, &filter));
+    }
+
+    #[test]
+    fn recorded_seed_replays_picks_without_requested_assets() {
+        let filter = RandomFilter::new(Some("USD"), None, None, true).unwrap();
+        let with_requested = generated_ids(&["BTCUSD", "ETHUSD"], 7, &filter);
+        let random_only = generated_ids(&[], 7, &filter);
+        // Every random pick is in the config generated with -a, alongside the requested assets
+        for asset in &random_only {
+            assert!(with_requested.contains(asset), "{} missing from {:?}", asset, with_requested);
+        }
+        let mut expected: Vec<String> = random_only.clone();
+        expected.extend(["BTCUSD".to_string(), "ETHUSD".to_string()]);
+        expected.sort();
+        expected.dedup();
+        assert_eq!(with_requested, expected);
+    }
+
+    #[test]
+    fn existing_assets_are_kept_out_of_merge_counts() {
+        let filter = RandomFilter::default();
+        let mut config = Config::default();
+        config.merge_assets(&available(), &OverrideRules::default());
+        let random = RandomSelection { count: 3, seed: 1, filter: &filter };
+        let err = generate(&mut config, &available(), &[], Some(&random), &OverrideRules::default());
+        assert!(matches!(err, Err(GenerateError::NoAssets)));
+    }
+}
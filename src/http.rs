@@ -0,0 +1,214 @@
+//! HTTP client settings, retries and errors shared by every request.
+
+use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response};
+use reqwest::header::{AUTHORIZATION, RETRY_AFTER};
+use reqwest::{Certificate, Proxy, StatusCode};
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use crate::VERSION;
+
+/// First retry delay; doubled on each further attempt
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Network settings applied to every HTTP client
+#[derive(Clone)]
+pub struct HttpSettings {
+    /// Timeout for a whole request, including reading the body
+    pub timeout: Duration,
+    /// Timeout for establishing a connection
+    pub connect_timeout: Duration,
+    /// Extra attempts after a 5xx, 429 or connection failure
+    pub retries: u32,
+    /// Proxy for all requests. Without it `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY` are used.
+    pub proxy: Option<String>,
+    /// PEM files with extra root certificates to trust
+    pub ca_certs: Vec<PathBuf>,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            retries: 3,
+            proxy: None,
+            ca_certs: Vec::new(),
+        }
+    }
+}
+
+/// Ways a request to the REST API can fail
+#[derive(Debug)]
+pub enum HttpError {
+    /// The server couldn't be reached, or the connection failed or timed out
+    Network(String),
+    /// The server rejected the auth token (401 or 403)
+    Auth(StatusCode),
+    /// Still rate limited (429) after all retries
+    RateLimited,
+    /// Any other unsuccessful status
+    Status(StatusCode),
+    /// The response body wasn't what we expected
+    Parse(String),
+    /// The client couldn't be set up, e.g. an unreadable CA certificate
+    Config(String),
+    /// `--offline` was given but there is no cached copy of the data
+    Offline(String),
+}
+
+impl HttpError {
+    /// Whether the failure is on the server or network side, so cached data may stand in
+    pub fn is_unreachable(&self) -> bool {
+        match self {
+            HttpError::Network(_) | HttpError::RateLimited => true,
+            HttpError::Status(status) => status.is_server_error(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Network(e) => write!(f, "Error making request: {}", e),
+            HttpError::Auth(status) => write!(
+                f,
+                "Server rejected the auth token ({}). Set a valid token with `stork-asset set-token <token>`",
+                status,
+            ),
+            HttpError::RateLimited => write!(f, "Rate limited by the server (429 Too Many Requests), try again later"),
+            HttpError::Status(status) => write!(f, "Server returned status {}", status),
+            HttpError::Parse(e) => write!(f, "Invalid response from server: {}", e),
+            HttpError::Config(e) | HttpError::Offline(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+impl From<HttpError> for String {
+    fn from(e: HttpError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Builder with the settings shared by every HTTP client
+pub fn client_builder(settings: &HttpSettings) -> Result<ClientBuilder, HttpError> {
+    let mut builder = Client::builder()
+        .user_agent(format!("stork-asset-cli/{}", VERSION))
+        .timeout(settings.timeout)
+        .connect_timeout(settings.connect_timeout);
+
+    if let Some(proxy) = &settings.proxy {
+        let proxy = Proxy::all(proxy)
+            .map_err(|e| HttpError::Config(format!("Invalid proxy '{}': {}", proxy, e)))?;
+        builder = builder.proxy(proxy);
+    }
+    for path in &settings.ca_certs {
+        let pem = fs::read(path)
+            .map_err(|e| HttpError::Config(format!("Failed to read CA certificate {}: {}", path.display(), e)))?;
+        let certs = Certificate::from_pem_bundle(&pem)
+            .map_err(|e| HttpError::Config(format!("Invalid CA certificate {}: {}", path.display(), e)))?;
+        if certs.is_empty() {
+            return Err(HttpError::Config(format!("No certificates found in {}", path.display())));
+        }
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    Ok(builder)
+}
+
+/// Blocking HTTP client with the given settings
+pub fn client(settings: &HttpSettings) -> Result<Client, HttpError> {
+    client_builder(settings)?
+        .build()
+        .map_err(|e| HttpError::Config(format!("Failed to create HTTP client: {}", e)))
+}
+
+/// GET request for a Stork REST API path, with the auth token if there is one
+pub fn rest_get(client: &Client, base_url: &str, path: &str, token: Option<&str>) -> RequestBuilder {
+    let mut request = client.get(format!("{}{}", base_url.trim_end_matches('/'), path));
+    if let Some(token) = token {
+        request = request.header(AUTHORIZATION, format!("Basic {}", token));
+    }
+    request
+}
+
+/// reqwest's own message leaves out the cause, e.g. "connection refused", so add it
+fn describe(e: &reqwest::Error, settings: &HttpSettings) -> String {
+    if e.is_timeout() {
+        return format!("request timed out after {}", humantime::format_duration(settings.timeout));
+    }
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        let cause_message = cause.to_string();
+        if !message.ends_with(&cause_message) {
+            message.push_str(&format!(": {}", cause_message));
+        }
+        source = cause.source();
+    }
+    message
+}
+
+/// Delay before retry number `attempt` (from 0), honouring a `Retry-After` header in seconds
+fn backoff(attempt: u32, response: Option<&Response>) -> Duration {
+    let retry_after = response
+        .and_then(|response| response.headers().get(RETRY_AFTER))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs);
+    retry_after
+        .unwrap_or_else(|| INITIAL_BACKOFF.saturating_mul(2u32.saturating_pow(attempt)))
+        .min(MAX_BACKOFF)
+}
+
+/// Send a request, retrying with exponential backoff on 5xx, 429 and connection failures.
+/// Returns the response if it was successful.
+pub fn send(request: RequestBuilder, settings: &HttpSettings) -> Result<Response, HttpError> {
+    let retries = settings.retries;
+    let mut attempt = 0;
+    loop {
+        let this_request = request.try_clone()
+            .ok_or_else(|| HttpError::Config("Request can't be retried".to_string()))?;
+        let result = this_request.send();
+        let retryable = match &result {
+            Ok(response) => response.status().is_server_error() || response.status() == StatusCode::TOO_MANY_REQUESTS,
+            Err(e) => e.is_connect() || e.is_timeout(),
+        };
+
+        if retryable && attempt < retries {
+            let delay = backoff(attempt, result.as_ref().ok());
+            let reason = match &result {
+                Ok(response) => response.status().to_string(),
+                Err(e) => describe(e, settings),
+            };
+            eprintln!("Request failed ({}), retrying in {}...", reason, humantime::format_duration(delay));
+            thread::sleep(delay);
+            attempt += 1;
+            continue;
+        }
+
+        let response = result.map_err(|e| HttpError::Network(describe(&e, settings)))?;
+        return match response.status() {
+            status if status.is_success() => Ok(response),
+            status @ (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => Err(HttpError::Auth(status)),
+            StatusCode::TOO_MANY_REQUESTS => Err(HttpError::RateLimited),
+            status => Err(HttpError::Status(status)),
+        };
+    }
+}
+
+/// Send a request with retries and parse the JSON response body
+pub fn get_json<T: DeserializeOwned>(request: RequestBuilder, settings: &HttpSettings) -> Result<T, HttpError> {
+    send(request, settings)?
+        .json()
+        .map_err(|e| HttpError::Parse(e.to_string()))
+}
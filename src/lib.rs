@@ -0,0 +1,37 @@
+//! Stork asset tooling: encode asset IDs, query the Stork REST API, and build, validate and
+//! compare asset configuration files. The `stork-asset` CLI is a thin wrapper over this crate.
+//!
+//! ```no_run
+//! use stork_asset::config::{Config, OverrideRules};
+//! use stork_asset::generate::generate;
+//! use stork_asset::StorkClient;
+//!
+//! let client = StorkClient::new(stork_asset::client::PROD_BASE_URL, Some("my-token"))?;
+//! let available = client.available_assets()?;
+//!
+//! let mut config = Config::default();
+//! let rules = OverrideRules::default();
+//! generate(&mut config, &available, &["BTCUSD".to_string()], None, &rules)?;
+//! config.save("assets.yaml".as_ref())?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+#![warn(missing_docs)]
+
+pub mod asset;
+pub mod client;
+pub mod config;
+pub mod diff;
+pub mod encode;
+pub mod generate;
+pub mod http;
+pub mod prices;
+pub mod selection;
+pub mod validate;
+pub mod verify;
+
+pub use client::StorkClient;
+pub use encode::calculate_encoded_asset_id;
+
+/// Version of this crate, sent in the `User-Agent` header
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -1,16 +1,46 @@
-use clap::{Parser, Subcommand};
-use rand::seq::SliceRandom;
-use reqwest::blocking::Client;
-use serde_derive::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::{ArgValueCompleter, CompleteEnv, Shell};
+use rand::Rng;
 use std::fs;
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
-use tiny_keccak::{Hasher, Keccak};
-use strsim::jaro_winkler;
 use colored::*;
-use std::process::Command;
+use std::time::Duration;
 
-const VERSION: &str = "0.2.1";
+use stork_asset::asset::{find_similar_assets, Asset, AssetFilter, Category};
+use stork_asset::config::{parse_asset_specs, AssetSettings, Config, OverrideRules};
+use stork_asset::diff::{self, diff_configs, diff_live};
+use stork_asset::encode::{encoded_asset_index, parse_encoded_asset_id};
+use stork_asset::generate::{generate, GenerateError, RandomSelection};
+use stork_asset::http::HttpSettings;
+use stork_asset::prices::PriceUpdate;
+use stork_asset::selection::RandomFilter;
+use stork_asset::validate::{validate_config, Issue, Severity};
+use stork_asset::verify::{parse_public_key, parse_signed_prices, verify_signed_price, VerifyReport};
+use stork_asset::{calculate_encoded_asset_id, StorkClient, VERSION};
+
+mod auth;
+mod cache;
+mod completions;
+mod exit;
+mod output;
+mod snapshot;
+mod subscribe;
+mod update;
+
+use auth::{
+    active_profile, delete_keyring_token, set_keyring_token, ActiveProfile, AuthConfig, ProfileOverrides,
+    DEV_PROFILE,
+};
+use cache::CacheOptions;
+use exit::{fail, fail_http, ExitCode};
+use output::{render_assets, render_diff, render_prices, write_output, CategoryArg, GroupBy, OutputArgs};
+use snapshot::Snapshot;
+use subscribe::{websocket_url, SubscribeOptions};
+use update::Channel;
+
+/// Releases shown by `update --list`
+const RELEASES_LISTED: usize = 10;
 
 #[derive(Parser)]
 #[command(name = "stork-asset")]
@@ -19,6 +49,99 @@ const VERSION: &str = "0.2.1";
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// Use a named authentication profile instead of the active one
+    #[arg(long = "profile", global = true)]
+    profile: Option<String>,
+
+    /// Use development environment (shorthand for --profile dev)
+    #[arg(long = "dev", global = true, conflicts_with = "profile")]
+    dev: bool,
+
+    /// Auth token to use instead of the environment or saved profile
+    #[arg(long = "token", global = true, conflicts_with = "token_file")]
+    token: Option<String>,
+
+    /// Read the auth token from a file
+    #[arg(long = "token-file", global = true)]
+    token_file: Option<PathBuf>,
+
+    /// REST API base URL to use instead of the environment or saved profile
+    #[arg(long = "base-url", global = true, conflicts_with = "region")]
+    base_url: Option<String>,
+
+    /// Stork region to connect to, e.g. jp (replaces the region in the profile's base URL)
+    #[arg(long = "region", global = true)]
+    region: Option<String>,
+
+    /// Fetch the asset list even if a fresh cached copy exists
+    #[arg(long = "refresh", global = true, conflicts_with = "offline")]
+    refresh: bool,
+
+    /// Use the cached asset list without contacting the REST API
+    #[arg(long = "offline", global = true)]
+    offline: bool,
+
+    /// Timeout for each HTTP request, e.g. 10s or 1m
+    #[arg(long = "timeout", global = true, default_value = "30s", value_parser = humantime::parse_duration)]
+    timeout: Duration,
+
+    /// Times to retry a request after a 5xx or 429 response or a connection failure
+    #[arg(long = "retries", global = true, default_value_t = 3)]
+    retries: u32,
+
+    /// Proxy URL for all requests [default: from HTTP_PROXY / HTTPS_PROXY]
+    #[arg(long = "proxy", global = true)]
+    proxy: Option<String>,
+
+    /// Extra CA certificate (PEM) to trust, e.g. for a TLS-intercepting proxy. Can be repeated.
+    #[arg(long = "ca-cert", global = true)]
+    ca_cert: Vec<PathBuf>,
+
+    /// How long a cached asset list stays fresh, e.g. 30m or 1d
+    #[arg(long = "cache-ttl", global = true, default_value = "1h", value_parser = humantime::parse_duration)]
+    cache_ttl: Duration,
+}
+
+impl Cli {
+    /// Profile explicitly requested on the command line, if any
+    fn requested_profile(&self) -> Option<&str> {
+        if self.dev {
+            Some(DEV_PROFILE)
+        } else {
+            self.profile.as_deref()
+        }
+    }
+
+    /// Profile overrides given on the command line
+    fn overrides(&self) -> ProfileOverrides {
+        ProfileOverrides {
+            token: self.token.clone(),
+            token_file: self.token_file.clone(),
+            base_url: self.base_url.clone(),
+            region: self.region.clone(),
+        }
+    }
+
+    /// Network settings for all HTTP requests
+    fn http_settings(&self) -> HttpSettings {
+        HttpSettings {
+            timeout: self.timeout,
+            retries: self.retries,
+            proxy: self.proxy.clone(),
+            ca_certs: self.ca_cert.clone(),
+            ..HttpSettings::default()
+        }
+    }
+
+    /// How the cached asset list may be used
+    fn cache_options(&self) -> CacheOptions {
+        CacheOptions {
+            ttl: self.cache_ttl,
+            refresh: self.refresh,
+            offline: self.offline,
+        }
+    }
 }
 
 #[derive(Subcommand)]
@@ -29,39 +152,94 @@ enum Commands {
         /// Show encoded asset IDs
         #[arg(short = 'e', long = "encoded")]
         show_encoded: bool,
-         /// Output in JSON format
-         #[arg(short = 'j', long = "json", conflicts_with = "csv", conflicts_with = "md", group = "output_format")]
-         json: bool,
-         /// Output in CSV format
-         #[arg(short = 'c', long = "csv", conflicts_with = "json", conflicts_with = "md", group = "output_format")]
-         csv: bool,
-         /// Output in Markdown table format
-         #[arg(short = 'm', long = "md", conflicts_with = "json", conflicts_with = "csv", group = "output_format")]
-         md: bool,
-         /// Output file (only valid with --json, --csv, or --md)
-         #[arg(short = 'o', long = "outfile", requires = "output_format")]
-         outfile: Option<PathBuf>,
-         /// Use development environment
-         #[arg(long = "dev")]
-         dev: bool,
+        /// Group assets by quote currency, base asset or category
+        #[arg(short = 'g', long = "group-by", value_enum)]
+        group_by: Option<GroupBy>,
+        /// Only show assets quoted in these currencies, e.g. USD,USDT
+        #[arg(long = "quote", value_delimiter = ',')]
+        quote: Vec<String>,
+        /// Only show assets with these base assets, e.g. BTC,ETH
+        #[arg(long = "base", value_delimiter = ',')]
+        base: Vec<String>,
+        /// Only show assets in these categories
+        #[arg(long = "category", value_enum, value_delimiter = ',')]
+        category: Vec<CategoryArg>,
+        /// Hide suffixed variants such as SOLUSD_RR
+        #[arg(long = "no-variants")]
+        no_variants: bool,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Get the latest prices for assets
+    #[command(aliases = ["price", "latest"])]
+    Prices {
+        /// Comma-separated list of asset IDs
+        #[arg(add = ArgValueCompleter::new(completions::asset_ids))]
+        assets: String,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Stream live prices over the Stork WebSocket API
+    #[command(aliases = ["sub", "stream"])]
+    Subscribe {
+        /// Comma-separated list of asset IDs
+        #[arg(add = ArgValueCompleter::new(completions::asset_ids))]
+        assets: String,
+        /// Output one JSON object per line
+        #[arg(long = "ndjson")]
+        ndjson: bool,
+        /// WebSocket URL [default: derived from the profile's base URL]
+        #[arg(long = "url")]
+        url: Option<String>,
+        /// Stop after this long, e.g. 30s or 5m
+        #[arg(short = 'd', long = "duration", value_parser = humantime::parse_duration)]
+        duration: Option<Duration>,
+        /// Stop after this many price updates
+        #[arg(short = 'n', long = "count")]
+        count: Option<usize>,
+        /// Exit instead of reconnecting when the connection drops
+        #[arg(long = "no-reconnect")]
+        no_reconnect: bool,
+    },
+    /// Verify Stork signed prices offline against a trusted public key
+    Verify {
+        /// Signed price JSON file (reads stdin if omitted or "-")
+        file: Option<PathBuf>,
+        /// Trusted Stork signer, as an EVM address or secp256k1 public key
+        #[arg(short = 'k', long = "public-key")]
+        public_key: String,
+        /// Asset ID for a bare signed price object that doesn't include one
+        #[arg(short = 'a', long = "asset", add = ArgValueCompleter::new(completions::asset_ids))]
+        asset: Option<String>,
+        /// Output in JSON format
+        #[arg(short = 'j', long = "json")]
+        json: bool,
     },
     /// Check if assets are available
     #[command(name = "check-assets", aliases = ["check"])]
     CheckAssets{
         /// Comma-separated list of asset IDs
+        #[arg(add = ArgValueCompleter::new(completions::asset_ids))]
         assets: String,
-        /// Use development environment
-        #[arg(long = "dev")]
-        dev: bool,
     },
     /// Get encoded asset ID(s)
     #[command(name = "get-encoded", aliases = ["get-enc", "enc", "encoded", "encode"])]
     GetEncodedAssets{
         /// Comma-separated list of asset IDs
+        #[arg(add = ArgValueCompleter::new(completions::asset_ids))]
         assets: String,
-        /// Use development environment
-        #[arg(long = "dev")]
-        dev: bool,
+    },
+    /// Look up the asset IDs behind encoded asset IDs
+    #[command(aliases = ["dec"])]
+    Decode {
+        /// Encoded asset IDs, e.g. 0x7404e3d1... [default: read from --file or stdin]
+        encoded: Vec<String>,
+        /// File with encoded asset IDs, one per line ("-" for stdin)
+        #[arg(short = 'f', long = "file", conflicts_with = "encoded")]
+        file: Option<PathBuf>,
+        /// Output in JSON format
+        #[arg(short = 'j', long = "json")]
+        json: bool,
     },
     /// Generate an asset configuration file
     #[command(aliases = ["gen", "generate", "gen-config", "gen-conf"])]
@@ -73,21 +251,98 @@ enum Commands {
         /// Number of random assets to include
         #[arg(short = 'r', long = "random")]
         random: Option<usize>,
+
+        /// Seed for the random selection, to reproduce an earlier config [default: random]
+        #[arg(short = 's', long = "seed", requires = "random")]
+        seed: Option<u64>,
+
+        #[command(flatten)]
+        random_filter: RandomFilterArgs,
         
-        /// Comma-separated list of assets to include
-        #[arg(short = 'a', long = "assets")]
+        /// Comma-separated list of assets to include, optionally with per-asset
+        /// settings as ID:FALLBACK:PERCENT (e.g. BTCUSD:30:0.1,ETHUSD::0.5)
+        #[arg(short = 'a', long = "assets", add = ArgValueCompleter::new(completions::asset_ids))]
         assets: Option<String>,
 
-        /// Fallback period in seconds
-        #[arg(short = 'f', long = "fallback", default_value = "60")]
-        fallback_period: u64,
+        #[command(flatten)]
+        settings: AssetSettingsArgs,
 
-        /// Percent change threshold
-        #[arg(short = 'p', long = "percent", default_value = "1.0")]
-        percent_change: f64,
-        /// Use development environment
-        #[arg(long = "dev")]
-        dev: bool,
+        /// Merge into the output file if it exists, keeping its assets and their settings
+        #[arg(long = "merge")]
+        merge: bool,
+    },
+    /// Add or update assets in an existing asset configuration file
+    Add {
+        /// Asset configuration file
+        file: PathBuf,
+        /// Comma-separated list of assets to add, optionally with per-asset
+        /// settings as ID:FALLBACK:PERCENT (e.g. BTCUSD:30:0.1,ETHUSD::0.5)
+        #[arg(add = ArgValueCompleter::new(completions::asset_ids))]
+        assets: String,
+        #[command(flatten)]
+        settings: AssetSettingsArgs,
+    },
+    /// Remove assets from an asset configuration file
+    #[command(aliases = ["rm"])]
+    Remove {
+        /// Asset configuration file
+        file: PathBuf,
+        /// Comma-separated list of assets to remove
+        #[arg(add = ArgValueCompleter::new(completions::asset_ids))]
+        assets: String,
+    },
+    /// Check an asset configuration file for mistakes and suspicious settings
+    #[command(aliases = ["lint"])]
+    Validate {
+        /// Asset configuration file
+        file: PathBuf,
+        /// Don't check that assets are still available from the REST API
+        #[arg(long = "skip-live")]
+        skip_live: bool,
+        /// Treat warnings as errors
+        #[arg(long = "strict")]
+        strict: bool,
+        /// Output in JSON format
+        #[arg(short = 'j', long = "json")]
+        json: bool,
+    },
+    /// Compare two asset configs, or a config against the live asset list
+    Diff {
+        /// Original asset configuration file
+        old: PathBuf,
+        /// New asset configuration file
+        #[arg(required_unless_present = "live", conflicts_with = "live")]
+        new: Option<PathBuf>,
+        /// Compare against the assets currently listed by the REST API to find delisted assets
+        #[arg(short = 'l', long = "live")]
+        live: bool,
+        /// Output in JSON format
+        #[arg(short = 'j', long = "json", conflicts_with = "md")]
+        json: bool,
+        /// Output in Markdown table format
+        #[arg(short = 'm', long = "md")]
+        md: bool,
+    },
+    /// Save the current asset list, to track added and delisted assets over time
+    Snapshot {
+        /// Directory to keep snapshots in [default: per base URL under the data directory]
+        #[arg(short = 'd', long = "dir")]
+        dir: Option<PathBuf>,
+    },
+    /// Show assets added or delisted between snapshots, or since the latest one
+    Changes {
+        /// Snapshot to compare from: latest, previous, a snapshot name or a file
+        #[arg(long = "from", default_value = "latest")]
+        from: String,
+        /// Snapshot to compare to [default: the live asset list]
+        #[arg(long = "to")]
+        to: Option<String>,
+        /// Directory snapshots are kept in [default: per base URL under the data directory]
+        #[arg(short = 'd', long = "dir")]
+        dir: Option<PathBuf>,
+        /// Output in JSON format
+        #[arg(short = 'j', long = "json")]
+        json: bool,
     },
     /// Check for updates and install the latest version
     #[command(aliases = ["upgrade"])]
@@ -95,25 +350,186 @@ enum Commands {
         /// Force update without version check
         #[arg(short = 'f', long = "force")]
         force: bool,
+        /// Restore the version replaced by the last update
+        #[arg(long = "rollback", conflicts_with = "force")]
+        rollback: bool,
+        /// Only report whether an update is available, exiting with status 8 if there is one
+        #[arg(long = "check", conflicts_with_all = ["force", "rollback"])]
+        check: bool,
+        /// Install this version instead of the latest, e.g. 0.3.0 (may be older than the current one, but
+        /// releases up to 0.2.1 aren't signed and can't be installed)
+        #[arg(long = "version", conflicts_with_all = ["rollback", "check"])]
+        version: Option<String>,
+        /// Releases to consider when looking for the latest version
+        #[arg(long = "channel", value_enum, default_value_t = Channel::Stable, conflicts_with = "version")]
+        channel: Channel,
+        /// Show recent releases and their notes
+        #[arg(long = "list", conflicts_with_all = ["force", "rollback", "check", "version"])]
+        list: bool,
+    },
+    /// Save the auth token used for the Stork REST API
+    #[command(name = "set-token")]
+    SetToken {
+        /// Stork auth token
+        token: String,
+        /// Store the token in the OS keyring instead of the config file
+        #[arg(short = 'k', long = "keyring")]
+        keyring: bool,
+    },
+    /// Show the auth token that will be used
+    #[command(name = "get-token")]
+    GetToken,
+    /// Remove the saved auth token from the config file and OS keyring
+    #[command(name = "clear-token")]
+    ClearToken,
+    /// Show which profile and token are in use and whether the token is accepted
+    #[command(name = "whoami", aliases = ["auth-status"])]
+    Whoami,
+    /// Manage named authentication profiles
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommands,
+    },
+    /// Manage the cached asset list
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+    /// Print a shell completion script, e.g. `source <(stork-asset completions bash)`
+    Completions {
+        /// Shell to complete for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print the man page, or write pages for every command to a directory
+    Man {
+        /// Directory to write one page per command to [default: print the main page]
+        #[arg(short = 'd', long = "dir")]
+        dir: Option<PathBuf>,
     },
 }
 
-#[derive(Serialize, Deserialize, Default)]
-struct AuthConfig {
-    auth_token: Option<String>,
+impl Commands {
+    /// Whether the command never touches the network, so a background update check would be
+    /// its only connection
+    fn is_local(&self) -> bool {
+        match self {
+            Commands::Verify { .. }
+            | Commands::Remove { .. }
+            | Commands::SetToken { .. }
+            | Commands::GetToken
+            | Commands::ClearToken
+            | Commands::Profile { .. }
+            | Commands::Cache { .. } => true,
+            Commands::Diff { live, .. } => !live,
+            Commands::Validate { skip_live, .. } => *skip_live,
+            Commands::Changes { to, .. } => to.is_some(),
+            _ => false,
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-struct AssetConfig {
-    asset_id: String,
-    fallback_period_sec: u64,
-    percent_change_threshold: f64,
-    encoded_asset_id: String,
+/// Options narrowing the pool random assets are picked from
+#[derive(Args)]
+struct RandomFilterArgs {
+    /// Only pick random assets quoted in this currency, e.g. USD or USDT
+    #[arg(long = "quote", requires = "random")]
+    quote: Option<String>,
+
+    /// Only pick random assets whose base asset matches this regex, e.g. '^(BTC|ETH|SOL)$'
+    #[arg(long = "base-regex", requires = "random")]
+    base_regex: Option<String>,
+
+    /// Comma-separated asset IDs or glob patterns never to pick at random
+    #[arg(long = "exclude", requires = "random")]
+    exclude: Option<String>,
+
+    /// Make major assets (BTC, ETH, SOL, ...) ten times more likely to be picked
+    #[arg(long = "prefer-majors", requires = "random")]
+    prefer_majors: bool,
+}
+
+/// Options deciding the fallback period and threshold of generated assets
+#[derive(Args)]
+struct AssetSettingsArgs {
+    /// YAML file mapping asset IDs or glob patterns to fallback_period_sec
+    /// and/or percent_change_threshold
+    #[arg(long = "overrides")]
+    overrides: Option<PathBuf>,
+
+    /// Settings for all assets matching a glob pattern, as PATTERN:FALLBACK:PERCENT
+    /// (e.g. '*USDT:30:0.5'). May be repeated
+    #[arg(long = "rule")]
+    rules: Vec<String>,
+
+    /// Fallback period in seconds [default: profile setting or 60]
+    #[arg(short = 'f', long = "fallback")]
+    fallback_period: Option<u64>,
+
+    /// Percent change threshold [default: profile setting or 1.0]
+    #[arg(short = 'p', long = "percent")]
+    percent_change: Option<f64>,
+}
+
+impl AssetSettingsArgs {
+    /// Combine these options, the profile defaults and any inline asset settings
+    fn override_rules(&self, profile: &ActiveProfile, asset_specs: &[(String, AssetSettings)]) -> Result<OverrideRules, String> {
+        let mut override_rules = OverrideRules::with_defaults(AssetSettings {
+            fallback_period_sec: self.fallback_period.or(profile.fallback_period_sec),
+            percent_change_threshold: self.percent_change.or(profile.percent_change_threshold),
+        });
+        if let Some(path) = &self.overrides {
+            override_rules.load_file(path)?;
+        }
+        for rule in &self.rules {
+            override_rules.add_rule(rule)?;
+        }
+        for (asset_id, settings) in asset_specs {
+            override_rules.add_inline(asset_id, *settings);
+        }
+        Ok(override_rules)
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-struct Config {
-    assets: BTreeMap<String, AssetConfig>,
+#[derive(Subcommand)]
+enum CacheCommands {
+    /// Delete all cached asset lists
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// Add a profile, or update the given settings of an existing one
+    Add {
+        /// Profile name
+        name: String,
+        /// Stork auth token
+        #[arg(short = 't', long = "token")]
+        token: Option<String>,
+        /// REST API base URL
+        #[arg(short = 'u', long = "base-url")]
+        base_url: Option<String>,
+        /// Default fallback period in seconds for generated configs
+        #[arg(short = 'f', long = "fallback")]
+        fallback_period: Option<u64>,
+        /// Default percent change threshold for generated configs
+        #[arg(short = 'p', long = "percent")]
+        percent_change: Option<f64>,
+    },
+    /// List profiles
+    #[command(aliases = ["ls"])]
+    List,
+    /// Make a profile the active one
+    Use {
+        /// Profile name
+        name: String,
+    },
+    /// Remove a profile
+    #[command(aliases = ["rm"])]
+    Remove {
+        /// Profile name
+        name: String,
+    },
 }
 
 fn validate_output_path(path: &str) -> Result<(), String> {
@@ -130,147 +546,65 @@ fn validate_output_path(path: &str) -> Result<(), String> {
     Ok(())
 }
 
-fn calculate_encoded_asset_id(asset_id: &str) -> String {
-    let mut keccak = Keccak::v256();
-    let mut output = [0u8; 32];
-    keccak.update(asset_id.as_bytes());
-    keccak.finalize(&mut output);
-    format!("0x{}", hex::encode(output))
-}
-
-fn get_available_assets(dev: bool) -> Result<Vec<String>, String> {
-    let client = Client::new();
-    
-    let base_url = if dev {
-        "https://rest.dev.jp.stork-oracle.network"
-    } else {
-        "https://rest.jp.stork-oracle.network"
-    };
-
-    match client
-        .get(format!("{}/v1/prices/assets", base_url))
-        .send()
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                let response: serde_json::Value = response.json().unwrap();
-                if let Some(assets) = response["data"].as_array() {
-                    Ok(assets
-                        .iter()
-                        .filter_map(|a| a.as_str().map(String::from))
-                        .collect())
-                } else {
-                    Err("Invalid response format from server".to_string())
-                }
-            } else {
-                Err(format!("Server returned status {}", response.status()))
-            }
+/// Read a whole file, or stdin if `path` is omitted or "-"
+fn read_input(path: Option<PathBuf>) -> Result<String, String> {
+    match path.filter(|path| path.as_os_str() != "-") {
+        Some(path) => fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e)),
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)
+                .map(|_| input)
+                .map_err(|e| format!("Failed to read stdin: {}", e))
         }
-        Err(e) => Err(format!("Error making request: {}", e)),
     }
 }
 
-fn find_similar_assets(target: &str, available_assets: &[String], limit: usize) -> Vec<String> {
-    const HARD_LIMIT: usize = 10;  // Maximum number of results we'll ever return
-    let target = target.to_uppercase();
-    
-    // First, collect all direct substring matches (up to HARD_LIMIT)
-    let mut exact_matches: Vec<String> = available_assets.iter()
-        .filter(|asset| asset.to_uppercase().contains(&target))
-        .take(HARD_LIMIT)  // Never return more than HARD_LIMIT matches
-        .cloned()
-        .collect();
-    
-    // If we have any exact matches, return them (already limited to HARD_LIMIT)
-    if !exact_matches.is_empty() {
-        return exact_matches;
-    }
-    
-    // For remaining slots, find the best partial matches
-    // (excluding assets that were already exact matches)
-    let remaining_slots = limit.min(HARD_LIMIT);  // Use the smaller of limit or HARD_LIMIT
-    let mut partial_matches: Vec<(f64, String)> = available_assets.iter()
-        .filter(|asset| !asset.to_uppercase().contains(&target))
-        .map(|asset| {
-            let asset_upper = asset.to_uppercase();
-            let mut score = jaro_winkler(&target, &asset_upper);
-            
-            // Boost score for prefix/suffix matches
-            if asset_upper.starts_with(&target) {
-                score += 0.3;  // Boost for prefix match
-            } else if asset_upper.ends_with(&target) {
-                score += 0.2;  // Boost for suffix match
-            }
-            
-            // Boost for partial word matches
-            if target.len() >= 3 && asset_upper.contains(&target[..target.len()-1]) {
-                score += 0.1;  // Small boost for almost containing the target
-            }
-            
-            (score, asset.clone())
-        })
-        .collect();
-    
-    // Sort partial matches by score
-    partial_matches.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
-    
-    // Add the best partial matches up to the remaining_slots
-    exact_matches.extend(
-        partial_matches.into_iter()
-            .take(remaining_slots)
-            .map(|(_, asset)| asset)
-    );
-    
-    exact_matches
+/// Load the auth config, exiting on any error
+fn load_auth_config() -> AuthConfig {
+    AuthConfig::load().unwrap_or_else(|e| fail(ExitCode::Error, e))
 }
 
-fn get_latest_version() -> Result<String, String> {
-    let client = reqwest::blocking::Client::builder()
-        .user_agent("stork-asset-cli")
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-
-    let response = client
-        .get("https://api.github.com/repos/henrymbaldwin/stork-asset-cli/releases/latest")
-        .send()
-        .map_err(|e| format!("Failed to check for updates: {}", e))?;
-    
-    if !response.status().is_success() {
-        return Err("Failed to get latest version information".to_string());
-    }
-
-    let release: serde_json::Value = response.json()
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
-    
-    release["tag_name"]
-        .as_str()
-        .map(|v| v.trim_start_matches('v').to_string())
-        .ok_or_else(|| "Invalid version format in response".to_string())
+/// Resolve the profile for this invocation, exiting on any error
+fn resolve_profile(requested: Option<&str>, overrides: &ProfileOverrides) -> ActiveProfile {
+    active_profile(requested, overrides).unwrap_or_else(|e| fail(ExitCode::Error, e))
 }
 
-// Add this new function to check if we have write permissions
-fn check_install_permissions() -> bool {
-    let install_path = Path::new("/usr/local/bin");
-    match fs::metadata(install_path) {
-        Ok(metadata) => {
-            // On Unix systems, check if we can write to the directory
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::MetadataExt;
-                let uid = unsafe { libc::getuid() };
-                metadata.uid() == uid || uid == 0
-            }
-            #[cfg(not(unix))]
-            {
-                true // On non-Unix systems, we'll try anyway
-            }
+/// Exit unless the running executable can be replaced, suggesting `sudo_command` if it needs root
+fn ensure_replaceable(exe: &Path, sudo_command: &str) {
+    if !update::can_replace(exe) {
+        eprintln!("Error: No permission to replace {}", exe.display());
+        if update::needs_root(exe) {
+            eprintln!("Please run with sudo:");
+            eprintln!("\n    {}\n", sudo_command);
         }
-        Err(_) => false,
+        ExitCode::FileWrite.exit();
     }
 }
 
+/// REST API client for a profile, exiting if it can't be set up
+fn stork_client(profile: &ActiveProfile, settings: &HttpSettings) -> StorkClient {
+    StorkClient::with_settings(&profile.base_url, profile.auth_token.as_deref(), settings.clone())
+        .unwrap_or_else(|e| fail_http(e))
+}
+
 fn main() {
+    // Answers completion requests from the scripts printed by `completions`, then exits
+    CompleteEnv::with_factory(Cli::command)
+        .var(completions::COMPLETE_ENV_VAR)
+        .complete();
+
     let cli = Cli::parse();
+    let requested_profile = cli.requested_profile().map(String::from);
+    let overrides = cli.overrides();
+    let cache_options = cli.cache_options();
+    let http_settings = cli.http_settings();
+    // Waits briefly for the check to be recorded when dropped at the end of main
+    let _update_check = match &cli.command {
+        Some(Commands::Update { .. } | Commands::Completions { .. } | Commands::Man { .. }) | None => None,
+        Some(command) if cli.offline || command.is_local() => None,
+        Some(_) => update::background_check(&http_settings),
+    };
     
     match cli.command {
         None => {
@@ -278,112 +612,123 @@ fn main() {
         }
         Some(cmd) => {
             match cmd {
-                Commands::GetAssets { show_encoded, json, csv, md, outfile, dev } => {
-                    match get_available_assets(dev) {
+                Commands::GetAssets { show_encoded, group_by, quote, base, category, no_variants, output } => {
+                    let profile = resolve_profile(requested_profile.as_deref(), &overrides);
+                    match cache::available_assets(&stork_client(&profile, &http_settings), &cache_options, false) {
                         Ok(mut assets) => {
                             // Sort assets alphabetically
                             assets.sort();
-                            
-                            // Prepare data in a format that's easy to transform
-                            let asset_data: Vec<(String, String)> = assets.iter()
-                                .map(|asset| (
-                                    asset.clone(),
-                                    if show_encoded {
-                                        calculate_encoded_asset_id(asset)
-                                    } else {
-                                        String::new()
-                                    }
-                                ))
+
+                            let filter = AssetFilter {
+                                quotes: quote.iter().map(|quote| quote.trim().to_uppercase()).collect(),
+                                bases: base.iter().map(|base| base.trim().to_uppercase()).collect(),
+                                categories: category.into_iter().map(Category::from).collect(),
+                                no_variants,
+                            };
+                            let assets: Vec<Asset> = assets.iter()
+                                .map(|asset| Asset::parse(asset))
+                                .filter(|asset| filter.matches(asset))
+                                .collect();
+
+                            write_output(render_assets(&assets, show_encoded, group_by, &output), output.outfile);
+                        }
+                        Err(e) => fail_http(e),
+                    }
+                },
+                Commands::Prices { assets, output } => {
+                    let profile = resolve_profile(requested_profile.as_deref(), &overrides);
+                    let requested: Vec<String> = assets.split(',')
+                        .map(|s| s.trim().to_uppercase())
+                        .filter(|s| !s.is_empty())
+                        .collect();
+
+                    match stork_client(&profile, &http_settings).latest_prices(&requested) {
+                        Ok(mut latest) => {
+                            let missing: Vec<&str> = requested.iter()
+                                .filter(|asset| !latest.contains_key(*asset))
+                                .map(|asset| asset.as_str())
+                                .collect();
+                            let prices: Vec<PriceUpdate> = requested.iter()
+                                .filter_map(|asset| latest.remove(asset))
                                 .collect();
 
-                            let output = if json {
-                                // JSON output
-                                let json_data: serde_json::Value = if show_encoded {
+                            write_output(render_prices(&prices, &output), output.outfile);
+                            if !missing.is_empty() {
+                                eprintln!("\nWarning: No prices returned for: {}", missing.join(", "));
+                                ExitCode::Unavailable.exit();
+                            }
+                        }
+                        Err(e) => fail_http(e),
+                    }
+                }
+                Commands::Subscribe { assets, ndjson, url, duration, count, no_reconnect } => {
+                    let profile = resolve_profile(requested_profile.as_deref(), &overrides);
+                    let options = SubscribeOptions {
+                        url: url.unwrap_or_else(|| websocket_url(&profile.base_url)),
+                        token: profile.auth_token,
+                        assets: assets.split(',')
+                            .map(|s| s.trim().to_uppercase())
+                            .filter(|s| !s.is_empty())
+                            .collect(),
+                        ndjson,
+                        reconnect: !no_reconnect,
+                        duration,
+                        count,
+                    };
+                    if let Err(e) = subscribe::run(&options) {
+                        fail(ExitCode::from(&e), e);
+                    }
+                }
+                Commands::Verify { file, public_key, asset, json } => {
+                    let trusted_key = parse_public_key(&public_key).unwrap_or_else(|e| fail(ExitCode::Error, e));
+
+                    let entries = read_input(file).and_then(|input| parse_signed_prices(&input, asset.as_deref()))
+                        .unwrap_or_else(|e| fail(ExitCode::Error, e));
+
+                    let reports: Vec<VerifyReport> = entries.iter()
+                        .map(|entry| verify_signed_price(entry, &trusted_key))
+                        .collect();
+                    let all_passed = reports.iter().all(|report| report.passed());
+
+                    if json {
+                        let json_data = serde_json::json!({
+                            "passed": all_passed,
+                            "results": reports.iter()
+                                .map(|report| {
                                     serde_json::json!({
-                                        "assets": asset_data.iter()
-                                            .map(|(asset, encoded)| {
-                                                serde_json::json!({
-                                                    "asset_id": asset,
-                                                    "encoded_id": encoded
-                                                })
-                                            })
+                                        "asset_id": report.asset_id,
+                                        "passed": report.passed(),
+                                        "checks": report.checks.iter()
+                                            .map(|check| serde_json::json!({
+                                                "check": check.name,
+                                                "passed": check.passed,
+                                                "detail": check.detail,
+                                            }))
                                             .collect::<Vec<_>>()
                                     })
-                                } else {
-                                    serde_json::json!({ "assets": assets })
-                                };
-                                serde_json::to_string_pretty(&json_data).unwrap()
-                            } else if csv {
-                                // CSV output
-                                let mut output = if show_encoded {
-                                    String::from("Asset ID, Encoded Asset ID\n")
-                                } else {
-                                    String::from("Asset ID\n")
-                                };
-                                
-                                for (asset, encoded) in &asset_data {
-                                    if show_encoded {
-                                        output.push_str(&format!("{},{}\n", asset, encoded));
-                                    } else {
-                                        output.push_str(&format!("{}\n", asset));
-                                    }
-                                }
-                                output
-                            } else if md {
-                                // Markdown table output
-                                let mut output = if show_encoded {
-                                    String::from("| Asset ID |  Encoded Asset ID |\n|----------|------------|\n")
-                                } else {
-                                    String::from("| Asset ID |\n|----------|\n")
-                                };
-                                
-                                for (asset, encoded) in &asset_data {
-                                    if show_encoded {
-                                        output.push_str(&format!("| {} | {} |\n", asset, encoded));
-                                    } else {
-                                        output.push_str(&format!("| {} |\n", asset));
-                                    }
-                                }
-                                output
-                            } else {
-                                // Default console output (original format)
-                                let mut output = String::from("Assets:\n\n");
-                                for (asset, encoded) in &asset_data {
-                                    if show_encoded {
-                                        output.push_str(&format!("{}: {}\n", asset, encoded));
-                                    } else {
-                                        output.push_str(&format!("{}\n", asset));
-                                    }
-                                }
-                                output.push_str(&format!("\nTotal Assets: {}", assets.len()));
-                                output
-                            };
-
-                            // Handle output destination
-                            if let Some(path) = outfile {
-                                // Create parent directory if it doesn't exist
-                                if let Some(parent) = path.parent() {
-                                    if !parent.exists() {
-                                        if let Err(e) = fs::create_dir_all(parent) {
-                                            println!("Error creating directory: {}", e);
-                                            return;
-                                        }
-                                    }
-                                }
-                                
-                                match fs::write(&path, output) {
-                                    Ok(_) => println!("Output written to {}", path.display()),
-                                    Err(e) => println!("Error writing to file: {}", e),
-                                }
-                            } else {
-                                println!("{}", output);
+                                })
+                                .collect::<Vec<_>>()
+                        });
+                        println!("{}", serde_json::to_string_pretty(&json_data).unwrap());
+                    } else {
+                        for report in &reports {
+                            let status = if report.passed() { "PASS".green() } else { "FAIL".red() };
+                            println!("{}: {}", report.asset_id.as_deref().unwrap_or("(unknown asset)"), status);
+                            for check in &report.checks {
+                                let mark = if check.passed { "ok".green() } else { "failed".red() };
+                                println!("  {:<17} {:<6}  {}", check.name, mark, check.detail);
                             }
+                            println!();
                         }
-                        Err(e) => println!("Error: {}", e),
                     }
-                },
-                Commands::CheckAssets { assets, dev } => {
-                    match get_available_assets(dev) {
+
+                    if !all_passed {
+                        ExitCode::Error.exit();
+                    }
+                }
+                Commands::CheckAssets { assets } => {
+                    let profile = resolve_profile(requested_profile.as_deref(), &overrides);
+                    match cache::available_assets(&stork_client(&profile, &http_settings), &cache_options, true) {
                         Ok(available_assets) => {
                             let mut failed = false;
                             for asset in assets.split(',').map(|s| s.trim()) {
@@ -407,27 +752,23 @@ fn main() {
                             }
                             if failed {
                                 println!("Note: Some assets were not found. Run {} to see all available assets.", "stork-asset get-assets".italic().yellow());
+                                ExitCode::Unavailable.exit();
                             }
                         }
-                        Err(e) => println!("Error: {}", e),
+                        Err(e) => fail_http(e),
                     }
                 },
-                Commands::GetEncodedAssets { assets, dev } => {
+                Commands::GetEncodedAssets { assets } => {
+                    let profile = resolve_profile(requested_profile.as_deref(), &overrides);
                     let mut invalid_assets = Vec::new();
-                    let available_assets = match get_available_assets(dev) {
-                        Ok(assets) => Some(assets),
-                        Err(e) => {
-                            let err_msg = format!("Unable to validate asset IDs: {}", e);
-                            None
-                        }
-                    };
+                    let available_assets = cache::available_assets(&stork_client(&profile, &http_settings), &cache_options, true);
 
                     // Print all asset IDs and their encodings first
                     for asset_id in assets.split(',').map(|s| s.trim()) {
                         let encoded = calculate_encoded_asset_id(asset_id);
                         println!("{}: {}", asset_id, encoded);
                         
-                        if let Some(ref available) = available_assets {
+                        if let Ok(ref available) = available_assets {
                             if !available.contains(&asset_id.to_string()) {
                                 invalid_assets.push(asset_id);
                             }
@@ -436,153 +777,623 @@ fn main() {
 
                     // Print any warnings after all assets
                     println!();
+                    if let Err(e) = available_assets {
+                        eprintln!("Warning: Unable to validate asset IDs: {}", e);
+                    }
                     if !invalid_assets.is_empty() {
-                        println!("Warning: The following asset IDs were invalid: {}", 
+                        eprintln!("Warning: The following asset IDs were invalid: {}", 
                             invalid_assets.join(", "));
+                        ExitCode::Unavailable.exit();
                     }
-                    if available_assets.is_none() {
-                        println!("Warning: Unable to validate asset IDs: {}", 
-                            "Error fetching available assets");
+                }
+                Commands::Decode { encoded, file, json } => {
+                    let input = if encoded.is_empty() {
+                        read_input(file).unwrap_or_else(|e| fail(ExitCode::Error, e))
+                    } else {
+                        encoded.join(",")
+                    };
+                    let encoded_ids: Vec<String> = input.split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|value| !value.is_empty())
+                        .map(parse_encoded_asset_id)
+                        .collect::<Result<_, _>>()
+                        .unwrap_or_else(|e| fail(ExitCode::Error, e));
+                    if encoded_ids.is_empty() {
+                        fail(ExitCode::Error, "No encoded asset IDs given");
+                    }
+
+                    let profile = resolve_profile(requested_profile.as_deref(), &overrides);
+                    let index = cache::available_assets(&stork_client(&profile, &http_settings), &cache_options, true)
+                        .map(|assets| encoded_asset_index(&assets))
+                        .unwrap_or_else(|e| fail_http(e));
+                    let unknown = encoded_ids.iter().filter(|encoded| !index.contains_key(*encoded)).count();
+
+                    if json {
+                        let json_data = serde_json::json!({
+                            "results": encoded_ids.iter()
+                                .map(|encoded| serde_json::json!({
+                                    "encoded_asset_id": encoded,
+                                    "asset_id": index.get(encoded),
+                                }))
+                                .collect::<Vec<_>>()
+                        });
+                        println!("{}", serde_json::to_string_pretty(&json_data).unwrap());
+                    } else {
+                        for encoded in &encoded_ids {
+                            match index.get(encoded) {
+                                Some(asset) => println!("{}: {}", encoded, asset),
+                                None => println!("{}: {}", encoded, "unknown".red()),
+                            }
+                        }
+                    }
+
+                    if unknown > 0 {
+                        eprintln!(
+                            "\nWarning: {} of {} encoded IDs don't match any available asset. They may be delisted, or try --refresh.",
+                            unknown, encoded_ids.len(),
+                        );
+                        ExitCode::Unavailable.exit();
                     }
                 }
                 Commands::GenerateConfig { 
                     output, 
                     random, 
+                    seed,
+                    random_filter,
                     assets, 
-                    fallback_period, 
-                    percent_change, 
-                    dev 
+                    settings,
+                    merge,
                 } => {
+                    // A bad output path is a usage mistake; 7 is kept for writes that actually fail
                     if let Err(e) = validate_output_path(&output) {
-                        println!("Error: {}", e);
-                        return;
+                        fail(ExitCode::Error, e);
                     }
 
-                    match get_available_assets(dev) {
-                        Ok(mut available_assets) => {
-                            let mut selected_assets = Vec::new();
-
-                            // First, add specifically requested assets
-                            if let Some(asset_list) = assets {
-                                let requested_assets: Vec<_> = asset_list.split(',')
-                                    .map(|s| s.trim().to_string())
-                                    .collect();
-                                
-                                // Validate all assets exist
-                                for asset in &requested_assets {
-                                    if !available_assets.contains(asset) {
-                                        println!("Error: Asset '{}' not found in available assets", asset);
-                                        return;
-                                    }
-                                    selected_assets.push(asset.clone());
-                                    // Remove from available_assets to prevent duplicates in random selection
-                                    if let Some(pos) = available_assets.iter().position(|x| x == asset) {
-                                        available_assets.swap_remove(pos);
-                                    }
+                    let profile = resolve_profile(requested_profile.as_deref(), &overrides);
+                    let asset_specs = assets.as_deref()
+                        .map(parse_asset_specs)
+                        .transpose()
+                        .unwrap_or_else(|e| fail(ExitCode::Error, e))
+                        .unwrap_or_default();
+                    let override_rules = settings.override_rules(&profile, &asset_specs).unwrap_or_else(|e| fail(ExitCode::Error, e));
+                    let random_filter = RandomFilter::new(
+                        random_filter.quote.as_deref(),
+                        random_filter.base_regex.as_deref(),
+                        random_filter.exclude.as_deref(),
+                        random_filter.prefer_majors,
+                    )
+                    .unwrap_or_else(|e| fail(ExitCode::Error, e));
+
+                    let output_path = Path::new(&output);
+                    let merging = merge && output_path.exists();
+                    let mut config = if merging {
+                        Config::load(output_path).unwrap_or_else(|e| fail(ExitCode::InvalidConfig, e))
+                    } else {
+                        Config::default()
+                    };
+
+                    let available_assets = cache::available_assets(&stork_client(&profile, &http_settings), &cache_options, false)
+                        .unwrap_or_else(|e| fail_http(e));
+                    let requested: Vec<String> = asset_specs.iter().map(|(asset, _)| asset.clone()).collect();
+                    // Always use a recorded seed, so any generated config can be replayed
+                    let random = random.map(|count| RandomSelection {
+                        count,
+                        seed: seed.unwrap_or_else(|| rand::thread_rng().gen()),
+                        filter: &random_filter,
+                    });
+
+                    let generated = match generate(&mut config, &available_assets, &requested, random.as_ref(), &override_rules) {
+                        Ok(generated) => generated,
+                        Err(e @ GenerateError::Unavailable(_)) => fail(ExitCode::Unavailable, e),
+                        Err(GenerateError::NoAssets) => fail(ExitCode::Error, "No assets selected. Use -a and/or -r to specify assets"),
+                    };
+
+                    let mut header = Vec::new();
+                    if let (Some(random), Some(candidates)) = (&random, generated.candidates) {
+                        if random.count > candidates {
+                            eprintln!("Warning: Requested {} additional random assets but only {} are available", 
+                                random.count, candidates);
+                        }
+                        let filter_flags = random_filter.describe();
+                        header.push(format!("Generated by stork-asset {}", VERSION));
+                        header.push(format!(
+                            "Random selection: -r {} --seed {}{}{} (from {} candidate assets)",
+                            random.count, random.seed, if filter_flags.is_empty() { "" } else { " " }, filter_flags, candidates,
+                        ));
+                    }
+
+                    // A merge keeps the existing header and records this selection after it
+                    config.add_header(&header);
+                    if let Err(e) = config.save(output_path) {
+                        fail(ExitCode::FileWrite, e);
+                    }
+
+                    if merging {
+                        println!("Added {} and updated {} assets in {} ({} assets total)", generated.added, generated.updated, output, config.assets.len());
+                    } else {
+                        println!("Successfully generated config with {} assets at {}", config.assets.len(), output);
+                    }
+                }
+                Commands::Add { file, assets, settings } => {
+                    let profile = resolve_profile(requested_profile.as_deref(), &overrides);
+                    let asset_specs = parse_asset_specs(&assets).unwrap_or_else(|e| fail(ExitCode::Error, e));
+                    let override_rules = settings.override_rules(&profile, &asset_specs).unwrap_or_else(|e| fail(ExitCode::Error, e));
+                    let mut config = Config::load(&file).unwrap_or_else(|e| fail(ExitCode::InvalidConfig, e));
+
+                    let new_assets: Vec<&str> = asset_specs.iter()
+                        .map(|(asset, _)| asset.as_str())
+                        .filter(|asset| !config.assets.contains_key(*asset))
+                        .collect();
+                    if !new_assets.is_empty() {
+                        match cache::available_assets(&stork_client(&profile, &http_settings), &cache_options, false) {
+                            Ok(available_assets) => {
+                                if let Some(asset) = new_assets.iter().find(|asset| !available_assets.iter().any(|a| a == *asset)) {
+                                    fail(ExitCode::Unavailable, format!("Asset '{}' not found in available assets", asset));
                                 }
                             }
+                            Err(e) => fail_http(e),
+                        }
+                    }
+
+                    let asset_ids: Vec<String> = asset_specs.into_iter().map(|(asset, _)| asset).collect();
+                    let (added, updated) = config.merge_assets(&asset_ids, &override_rules);
+                    match config.save(&file) {
+                        Ok(_) => println!("Added {} and updated {} assets in {} ({} assets total)", added, updated, file.display(), config.assets.len()),
+                        Err(e) => fail(ExitCode::FileWrite, e),
+                    }
+                }
+                Commands::Remove { file, assets } => {
+                    let mut config = Config::load(&file).unwrap_or_else(|e| fail(ExitCode::InvalidConfig, e));
+
+                    let asset_ids: Vec<String> = assets.split(',')
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty())
+                        .collect();
+                    let missing = config.remove_assets(&asset_ids);
+                    if !missing.is_empty() {
+                        eprintln!("Warning: Not in {}: {}", file.display(), missing.join(", "));
+                    }
 
-                            // Then add random assets if requested
-                            if let Some(n) = random {
-                                if n > 0 {
-                                    if n > available_assets.len() {
-                                        println!("Warning: Requested {} additional random assets but only {} are available", 
-                                            n, available_assets.len());
+                    match config.save(&file) {
+                        Ok(_) => println!("Removed {} assets from {} ({} assets remaining)", asset_ids.len() - missing.len(), file.display(), config.assets.len()),
+                        Err(e) => fail(ExitCode::FileWrite, e),
+                    }
+                }
+                Commands::Validate { file, skip_live, strict, json } => {
+                    let mut issues = Vec::new();
+                    // Set when availability couldn't be checked, so a CI gate can't pass without it
+                    let mut live_check_failure = None;
+                    match Config::load(&file) {
+                        Ok(config) => {
+                            let available = if skip_live {
+                                None
+                            } else {
+                                let available = match active_profile(requested_profile.as_deref(), &overrides) {
+                                    Ok(profile) => StorkClient::with_settings(&profile.base_url, profile.auth_token.as_deref(), http_settings.clone())
+                                        .and_then(|client| cache::available_assets(&client, &cache_options, true))
+                                        .map_err(|e| (ExitCode::from(&e), e.to_string())),
+                                    Err(e) => Err((ExitCode::Error, e)),
+                                };
+                                match available {
+                                    Ok(assets) => Some(assets),
+                                    Err((code, e)) => {
+                                        live_check_failure = Some(code);
+                                        issues.push(Issue {
+                                            severity: Severity::Error,
+                                            asset: None,
+                                            code: "live-check-failed",
+                                            message: format!("Could not check asset availability (use --skip-live to skip the check): {}", e),
+                                        });
+                                        None
                                     }
-                                    let mut rng = rand::thread_rng();
-                                    selected_assets.extend(
-                                        available_assets
-                                            .choose_multiple(&mut rng, n.min(available_assets.len()))
-                                            .cloned()
-                                    );
                                 }
-                            }
+                            };
+                            issues.extend(validate_config(&config, available.as_deref()));
+                        }
+                        Err(e) => issues.push(Issue {
+                            severity: Severity::Error,
+                            asset: None,
+                            code: "parse-error",
+                            message: e,
+                        }),
+                    }
 
-                            if selected_assets.is_empty() {
-                                println!("Error: No assets selected. Use -a and/or -r to specify assets");
-                                return;
-                            }
+                    let errors = issues.iter().filter(|issue| issue.severity == Severity::Error).count();
+                    let warnings = issues.len() - errors;
+                    let valid = errors == 0 && (!strict || warnings == 0);
 
-                            let mut config_map = BTreeMap::new();
-                            for asset_id in selected_assets {
-                                let encoded_id = calculate_encoded_asset_id(&asset_id);
-                                let asset_config = AssetConfig {
-                                    asset_id: asset_id.clone(),
-                                    fallback_period_sec: fallback_period,
-                                    percent_change_threshold: percent_change,
-                                    encoded_asset_id: encoded_id,
-                                };
-                                config_map.insert(asset_id, asset_config);
+                    if json {
+                        let json_data = serde_json::json!({
+                            "file": file.display().to_string(),
+                            "valid": valid,
+                            "errors": errors,
+                            "warnings": warnings,
+                            "issues": issues.iter()
+                                .map(|issue| serde_json::json!({
+                                    "severity": issue.severity.as_str(),
+                                    "asset": issue.asset,
+                                    "code": issue.code,
+                                    "message": issue.message,
+                                }))
+                                .collect::<Vec<_>>()
+                        });
+                        println!("{}", serde_json::to_string_pretty(&json_data).unwrap());
+                    } else {
+                        for issue in &issues {
+                            let severity = match issue.severity {
+                                Severity::Error => "error".red(),
+                                Severity::Warning => "warning".yellow(),
+                            };
+                            match &issue.asset {
+                                Some(asset) => println!("{} [{}] {}: {}", severity, issue.code, asset, issue.message),
+                                None => println!("{} [{}] {}", severity, issue.code, issue.message),
                             }
+                        }
+                        if !issues.is_empty() {
+                            println!();
+                        }
+                        let summary = format!("{}: {} errors, {} warnings", file.display(), errors, warnings);
+                        if valid {
+                            println!("{} {}", "OK".green(), summary);
+                        } else {
+                            println!("{} {}", "INVALID".red(), summary);
+                        }
+                    }
 
-                            let config = Config {
-                                assets: config_map,
-                            };
+                    if !valid {
+                        match live_check_failure {
+                            // Only report the failed check's code if nothing else is wrong with the config
+                            Some(code) if errors == 1 => code.exit(),
+                            _ => ExitCode::InvalidConfig.exit(),
+                        }
+                    }
+                }
+                Commands::Diff { old, new, live, json, md } => {
+                    let old_config = Config::load(&old).unwrap_or_else(|e| fail(ExitCode::InvalidConfig, e));
 
-                            let yaml_content = serde_yaml::to_string(&config)
-                                .expect("Failed to serialize to YAML");
-                            
-                            fs::write(&output, yaml_content)
-                                .map_err(|e| println!("Error writing file: {}", e))
-                                .ok();
-                            
-                            println!("Successfully generated config with {} assets at {}", config.assets.len(), output);
+                    let (diff, removed_label) = if live {
+                        let profile = resolve_profile(requested_profile.as_deref(), &overrides);
+                        match cache::available_assets(&stork_client(&profile, &http_settings), &cache_options, false) {
+                            Ok(live_assets) => (diff_live(&old_config, &live_assets), "delisted"),
+                            Err(e) => fail_http(e),
+                        }
+                    } else {
+                        let new = new.expect("clap requires NEW unless --live is given");
+                        match Config::load(&new) {
+                            Ok(new_config) => (diff_configs(&old_config, &new_config), "removed"),
+                            Err(e) => fail(ExitCode::InvalidConfig, e),
+                        }
+                    };
+
+                    if json {
+                        println!("{}", diff::render_json(&diff));
+                    } else if md {
+                        print!("{}", diff::render_markdown(&diff, removed_label));
+                    } else {
+                        println!("{}", render_diff(&diff, removed_label));
+                    }
+                }
+                Commands::Snapshot { dir } => {
+                    let profile = resolve_profile(requested_profile.as_deref(), &overrides);
+                    let dir = dir.map(Ok)
+                        .unwrap_or_else(|| snapshot::default_dir(&profile.base_url))
+                        .unwrap_or_else(|e| fail(ExitCode::Error, e));
+                    // A snapshot should reflect the live list, so bypass a fresh cache
+                    let options = CacheOptions { refresh: !cache_options.offline, ..cache_options };
+                    let snapshot = match cache::available_assets(&stork_client(&profile, &http_settings), &options, false) {
+                        Ok(assets) => Snapshot::new(&profile.base_url, assets),
+                        Err(e) => fail_http(e),
+                    };
+                    let previous = snapshot::resolve(&dir, "latest").and_then(|path| Snapshot::load(&path)).ok();
+                    match snapshot.save(&dir) {
+                        Ok(path) => {
+                            println!("Saved snapshot of {} assets to {}", snapshot.assets.len(), path.display());
+                            if let Some(previous) = previous {
+                                let changes = snapshot::compare(&previous, &snapshot);
+                                println!("{} added, {} delisted since {}", changes.added.len(), changes.removed.len(), previous.taken_at_rfc3339());
+                            }
                         }
-                        Err(e) => println!("Error: {}", e),
+                        Err(e) => fail(ExitCode::FileWrite, e),
                     }
                 }
-                Commands::Update { force } => {
+                Commands::Changes { from, to, dir, json } => {
+                    let profile = resolve_profile(requested_profile.as_deref(), &overrides);
+                    let dir = dir.map(Ok)
+                        .unwrap_or_else(|| snapshot::default_dir(&profile.base_url))
+                        .unwrap_or_else(|e| fail(ExitCode::Error, e));
+                    let from = snapshot::resolve(&dir, &from).and_then(|path| Snapshot::load(&path)).unwrap_or_else(|e| fail(ExitCode::Error, e));
+                    let live = to.is_none();
+                    let to = match to {
+                        Some(to) => snapshot::resolve(&dir, &to)
+                            .and_then(|path| Snapshot::load(&path))
+                            .unwrap_or_else(|e| fail(ExitCode::Error, e)),
+                        // A cached list may predate the snapshot, so compare against the live one
+                        None => {
+                            let options = CacheOptions { refresh: !cache_options.offline, ..cache_options };
+                            cache::available_assets(&stork_client(&profile, &http_settings), &options, false)
+                                .map(|assets| Snapshot::new(&profile.base_url, assets))
+                                .unwrap_or_else(|e| fail_http(e))
+                        }
+                    };
+
+                    let changes = snapshot::compare(&from, &to);
+                    if json {
+                        println!("{}", snapshot::render_json(&from, &to, &changes));
+                    } else {
+                        if live {
+                            println!("Changes since {}:\n", from.taken_at_rfc3339());
+                        } else {
+                            println!("Changes from {} to {}:\n", from.taken_at_rfc3339(), to.taken_at_rfc3339());
+                        }
+                        println!("{}", render_diff(&changes, "delisted"));
+                    }
+                }
+                Commands::Update { force, rollback, check, version, channel, list } => {
+                    let exe = update::current_exe().unwrap_or_else(|e| fail(ExitCode::Error, e));
+                    if rollback {
+                        ensure_replaceable(&exe, "sudo stork-asset update --rollback");
+                        match update::rollback(&exe) {
+                            Ok(_) => println!("Restored the previous version of {}", exe.display()),
+                            Err(e) => fail(ExitCode::FileWrite, e),
+                        }
+                        return;
+                    }
+
+                    if list {
+                        let releases = update::list_releases(&http_settings, channel)
+                            .unwrap_or_else(|e| fail(ExitCode::Network, format!("Could not list releases: {}", e)));
+                        for release in releases.iter().take(RELEASES_LISTED) {
+                            let published = release.published_at
+                                .map(|date| date.format("%Y-%m-%d").to_string())
+                                .unwrap_or_default();
+                            let mut labels = Vec::new();
+                            if release.prerelease {
+                                labels.push("pre-release".yellow());
+                            }
+                            if release.version() == VERSION {
+                                labels.push("installed".green());
+                            }
+                            let mut heading = format!("{}  {}", release.tag_name.bold(), published);
+                            for label in labels {
+                                heading.push_str(&format!("  ({})", label));
+                            }
+                            println!("{}", heading);
+                            for line in release.body.as_deref().unwrap_or_default().lines() {
+                                println!("    {}", line.trim_end());
+                            }
+                            println!();
+                        }
+                        return;
+                    }
+
                     println!("Checking for updates...");
                     
-                    match get_latest_version() {
-                        Ok(latest_version) => {
-                            if !force && latest_version == VERSION {
-                                println!("You're already running the latest version ({})", VERSION);
+                    let release = match &version {
+                        Some(version) => match update::find_release(&http_settings, version) {
+                            Ok(Some(release)) => Ok(release),
+                            Ok(None) => fail(ExitCode::Error, format!("No release tagged {}", update::tag_name(version))),
+                            Err(e) => Err(e),
+                        },
+                        None => update::latest_release(&http_settings, channel),
+                    };
+                    match release {
+                        Ok(release) => {
+                            let latest_version = release.semver().unwrap_or_else(|e| fail(ExitCode::Error, e));
+                            let current_version = update::current_version();
+                            if check {
+                                if latest_version > current_version {
+                                    println!("Update available: {} -> {}", current_version, latest_version);
+                                    ExitCode::UpdateAvailable.exit();
+                                }
+                                println!("You're already running the latest version ({})", current_version);
+                                return;
+                            }
+                            if !release.is_signed() {
+                                let hint = match version {
+                                    Some(_) => "Versions released before checksums were signed can't be pinned; pick a newer one from `stork-asset update --list`",
+                                    None => "Install it with the install script or cargo instead",
+                                };
+                                fail(ExitCode::Error, format!(
+                                    "{} has no signed SHA256SUMS file, so it can't be verified and installed with `update`. {}",
+                                    release.tag_name, hint,
+                                ));
+                            }
+                            if !force && latest_version == current_version {
+                                match version {
+                                    Some(_) => println!("You're already running version {}", VERSION),
+                                    None => println!("You're already running the latest version ({})", VERSION),
+                                }
                                 return;
                             }
                             
                             println!("Current version: {}", VERSION);
-                            println!("Latest version:  {}", latest_version);
+                            if version.is_some() {
+                                println!("Target version:  {}", latest_version);
+                            } else {
+                                println!("Latest version:  {}", latest_version);
+                            }
                             
-                            if !force && latest_version < VERSION.to_string() {
-                                println!("Warning: Latest version is older than current version");
+                            // Installing an older pinned version is deliberate, so only warn about the latest
+                            if !force && version.is_none() && latest_version < current_version {
+                                eprintln!("Warning: Latest version is older than current version");
                                 println!("Use --force to update anyway");
                                 return;
                             }
 
-                            if !check_install_permissions() {
-                                println!("Error: Insufficient permissions to perform update");
-                                println!("Please run with sudo:");
-                                println!("\n    sudo stork-asset update\n");
-                                return;
-                            }
-                            
-                            println!("Installing update...");
-                            
-                            // Modified install command with more robust grep pattern
-                            let install_cmd = r#"
-                                curl -fsSL https://raw.githubusercontent.com/HenryMBaldwin/stork-asset-cli/refs/heads/master/install.sh | sudo bash
-                            "#;
+                            ensure_replaceable(&exe, "sudo stork-asset update");
                             
-                            match Command::new("sh")
-                                .arg("-c")
-                                .arg(install_cmd)
-                                .status() 
-                            {
-                                Ok(status) => {
-                                    if status.success() {
-                                        println!("Successfully updated stork-asset-cli to version {}", latest_version);
-                                    } else {
-                                        println!("Failed to update. Please try again or update manually");
-                                    }
+                            println!("Downloading {}...", release.tag_name);
+                            let binary = update::download_verified(&release, &http_settings)
+                                .unwrap_or_else(|e| fail(ExitCode::Error, format!("Update failed: {}", e)));
+                            println!("Checksum and signature verified");
+
+                            if let Err(e) = update::install(&binary, &exe) {
+                                fail(ExitCode::FileWrite, e);
+                            }
+                            println!("Successfully updated stork-asset-cli to version {}", latest_version);
+                            println!("Run {} to go back to {}", "stork-asset update --rollback".italic().yellow(), VERSION);
+                        }
+                        Err(e) => fail(ExitCode::Network, format!("Could not check for updates: {}", e)),
+                    }
+                }
+                Commands::SetToken { token, keyring } => {
+                    let mut auth_config = load_auth_config();
+                    let name = auth_config.profile_name(requested_profile.as_deref());
+                    let token = token.trim().to_string();
+                    if keyring {
+                        if let Err(e) = set_keyring_token(&name, &token) {
+                            fail(ExitCode::Error, e);
+                        }
+                        // Drop any copy in the config file so the keyring token is the one used
+                        if let Some(profile) = auth_config.profiles.get_mut(&name) {
+                            if profile.auth_token.take().is_some() {
+                                if let Err(e) = auth_config.save() {
+                                    fail(ExitCode::FileWrite, e);
+                                }
+                            }
+                        }
+                        println!("Auth token saved to OS keyring for profile '{}'", name);
+                        return;
+                    }
+                    auth_config.profiles.entry(name.clone()).or_default().auth_token = Some(token);
+                    match auth_config.save() {
+                        Ok(_) => println!("Auth token saved for profile '{}'", name),
+                        Err(e) => fail(ExitCode::FileWrite, e),
+                    }
+                }
+                Commands::GetToken => {
+                    let profile = resolve_profile(requested_profile.as_deref(), &overrides);
+                    match profile.auth_token {
+                        Some(token) => println!("{}", token),
+                        None => fail(ExitCode::Auth, format!("No auth token set for profile '{}'. Use {} to set one.", profile.name, "stork-asset set-token <token>".italic().yellow())),
+                    }
+                }
+                Commands::ClearToken => {
+                    let mut auth_config = load_auth_config();
+                    let name = auth_config.profile_name(requested_profile.as_deref());
+                    let cleared_file = auth_config.profiles.get_mut(&name)
+                        .and_then(|profile| profile.auth_token.take())
+                        .is_some();
+                    if cleared_file {
+                        if let Err(e) = auth_config.save() {
+                            fail(ExitCode::FileWrite, e);
+                        }
+                    }
+                    let cleared_keyring = match delete_keyring_token(&name) {
+                        Ok(cleared) => cleared,
+                        Err(e) => {
+                            eprintln!("Warning: {}", e);
+                            false
+                        }
+                    };
+                    if cleared_file || cleared_keyring {
+                        println!("Auth token cleared for profile '{}'", name);
+                    } else {
+                        println!("No auth token set for profile '{}'", name);
+                    }
+                }
+                Commands::Whoami => {
+                    let profile = resolve_profile(requested_profile.as_deref(), &overrides);
+                    println!("Profile:      {}", profile.name);
+                    println!("Base URL:     {}", profile.base_url);
+                    println!("Token source: {}", profile.token_source);
+                    let Some(token) = &profile.auth_token else {
+                        println!("Status:       {}", "no token".yellow());
+                        ExitCode::Auth.exit();
+                    };
+                    println!("Token:        {} characters (hidden)", token.len());
+                    match stork_client(&profile, &http_settings).check_auth() {
+                        Ok(true) => println!("Status:       {}", "authenticated".green()),
+                        Ok(false) => {
+                            println!("Status:       {}", "rejected by server".red());
+                            ExitCode::Auth.exit();
+                        }
+                        Err(e) => {
+                            println!("Status:       {} ({})", "unknown".yellow(), e);
+                            ExitCode::from(&e).exit();
+                        }
+                    }
+                }
+                Commands::Cache { command } => {
+                    match command {
+                        CacheCommands::Clear => match cache::clear() {
+                            Ok(removed) => println!("Removed {} cached asset lists", removed),
+                            Err(e) => fail(ExitCode::FileWrite, e),
+                        },
+                    }
+                }
+                Commands::Completions { shell } => {
+                    if let Err(e) = completions::write_registration(shell, &mut io::stdout().lock()) {
+                        fail(ExitCode::Error, format!("Failed to write completions: {}", e));
+                    }
+                }
+                Commands::Man { dir } => {
+                    if let Err(e) = completions::write_man_pages(Cli::command(), dir.as_deref()) {
+                        fail(ExitCode::FileWrite, e);
+                    }
+                }
+                Commands::Profile { command } => {
+                    let mut auth_config = load_auth_config();
+                    match command {
+                        ProfileCommands::Add { name, token, base_url, fallback_period, percent_change } => {
+                            let profile = auth_config.profiles.entry(name.clone()).or_default();
+                            if token.is_some() {
+                                profile.auth_token = token.map(|t| t.trim().to_string());
+                            }
+                            if base_url.is_some() {
+                                profile.base_url = base_url.map(|url| url.trim_end_matches('/').to_string());
+                            }
+                            if fallback_period.is_some() {
+                                profile.fallback_period_sec = fallback_period;
+                            }
+                            if percent_change.is_some() {
+                                profile.percent_change_threshold = percent_change;
+                            }
+                            match auth_config.save() {
+                                Ok(_) => println!("Profile '{}' saved", name),
+                                Err(e) => fail(ExitCode::FileWrite, e),
+                            }
+                        }
+                        ProfileCommands::List => {
+                            let active = auth_config.profile_name(None);
+                            for name in auth_config.profile_names() {
+                                let profile = auth_config.profile(&name).unwrap_or_default();
+                                let marker = if name == active { "*".green() } else { " ".normal() };
+                                println!("{} {}", marker, name.bold());
+                                println!("    base url:  {}", profile.base_url.as_deref().unwrap_or("-"));
+                                println!("    token:     {}", if profile.auth_token.is_some() { "set".green() } else { "not set".yellow() });
+                                if let Some(fallback) = profile.fallback_period_sec {
+                                    println!("    fallback:  {}s", fallback);
+                                }
+                                if let Some(percent) = profile.percent_change_threshold {
+                                    println!("    percent:   {}", percent);
                                 }
-                                Err(e) => println!("Error during update: {}", e),
                             }
                         }
-                        Err(e) => println!("Error checking for updates: {}", e),
+                        ProfileCommands::Use { name } => {
+                            if auth_config.profile(&name).is_none() {
+                                fail(ExitCode::Error, format!("Unknown profile '{}'. Add it with {}", name, "stork-asset profile add <name>".italic().yellow()));
+                            }
+                            auth_config.active_profile = Some(name.clone());
+                            match auth_config.save() {
+                                Ok(_) => println!("Now using profile '{}'", name),
+                                Err(e) => fail(ExitCode::FileWrite, e),
+                            }
+                        }
+                        ProfileCommands::Remove { name } => {
+                            if auth_config.profiles.remove(&name).is_none() {
+                                fail(ExitCode::Error, format!("No saved profile named '{}'", name));
+                            }
+                            if auth_config.active_profile.as_deref() == Some(name.as_str()) {
+                                auth_config.active_profile = None;
+                            }
+                            match auth_config.save() {
+                                Ok(_) => println!("Profile '{}' removed", name),
+                                Err(e) => fail(ExitCode::FileWrite, e),
+                            }
+                        }
                     }
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,327 @@
+use chrono::Utc;
+use clap::{Args, ValueEnum};
+use colored::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use stork_asset::asset::{Asset, Category};
+use stork_asset::calculate_encoded_asset_id;
+use stork_asset::diff::ConfigDiff;
+use stork_asset::prices::{age_seconds, format_age, format_quantized_price, format_timestamp, PriceUpdate};
+
+use crate::exit::{fail, ExitCode};
+
+/// Output format flags shared by commands that print tabular data
+#[derive(Args)]
+pub struct OutputArgs {
+    /// Output in JSON format
+    #[arg(short = 'j', long = "json", conflicts_with = "csv", conflicts_with = "md", group = "output_format")]
+    pub json: bool,
+    /// Output in CSV format
+    #[arg(short = 'c', long = "csv", conflicts_with = "json", conflicts_with = "md", group = "output_format")]
+    pub csv: bool,
+    /// Output in Markdown table format
+    #[arg(short = 'm', long = "md", conflicts_with = "json", conflicts_with = "csv", group = "output_format")]
+    pub md: bool,
+    /// Output file (only valid with --json, --csv, or --md)
+    #[arg(short = 'o', long = "outfile", requires = "output_format")]
+    pub outfile: Option<PathBuf>,
+}
+
+/// Which part of an asset ID to group a listing by
+#[derive(Clone, Copy, ValueEnum)]
+pub enum GroupBy {
+    Quote,
+    Base,
+    Category,
+}
+
+impl GroupBy {
+    /// Column heading for the group in tables
+    fn label(&self) -> &'static str {
+        match self {
+            GroupBy::Quote => "Quote",
+            GroupBy::Base => "Base",
+            GroupBy::Category => "Category",
+        }
+    }
+
+    /// The value an asset is grouped under
+    fn key(&self, asset: &Asset) -> String {
+        match self {
+            GroupBy::Quote => asset.quote.clone().unwrap_or_else(|| "unknown".to_string()),
+            GroupBy::Base => asset.base.clone(),
+            GroupBy::Category => asset.category.to_string(),
+        }
+    }
+}
+
+/// `--category` values, mapped onto the library's [`Category`]
+#[derive(Clone, Copy, ValueEnum)]
+pub enum CategoryArg {
+    Crypto,
+    Fx,
+    Equity,
+    Rate,
+}
+
+impl From<CategoryArg> for Category {
+    fn from(category: CategoryArg) -> Self {
+        match category {
+            CategoryArg::Crypto => Category::Crypto,
+            CategoryArg::Fx => Category::Fx,
+            CategoryArg::Equity => Category::Equity,
+            CategoryArg::Rate => Category::Rate,
+        }
+    }
+}
+
+/// Render rows as CSV with a header line
+pub fn csv_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut output = format!("{}\n", headers.join(","));
+    for row in rows {
+        output.push_str(&format!("{}\n", row.join(",")));
+    }
+    output
+}
+
+/// Render rows as a Markdown table
+pub fn markdown_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut output = format!("| {} |\n", headers.join(" | "));
+    output.push_str(&format!("|{}\n", "----------|".repeat(headers.len())));
+    for row in rows {
+        output.push_str(&format!("| {} |\n", row.join(" | ")));
+    }
+    output
+}
+
+/// Print the output, or write it to `outfile` if one was given. Exits if the file can't be written.
+pub fn write_output(output: String, outfile: Option<PathBuf>) {
+    if let Some(path) = outfile {
+        // Create parent directory if it doesn't exist
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                if let Err(e) = fs::create_dir_all(parent) {
+                    fail(ExitCode::FileWrite, format!("Failed to create directory {}: {}", parent.display(), e));
+                }
+            }
+        }
+
+        match fs::write(&path, output) {
+            Ok(_) => println!("Output written to {}", path.display()),
+            Err(e) => fail(ExitCode::FileWrite, format!("Failed to write {}: {}", path.display(), e)),
+        }
+    } else {
+        println!("{}", output);
+    }
+}
+
+/// Render the asset list in the format selected by `output`, optionally grouped
+pub fn render_assets(assets: &[Asset], show_encoded: bool, group_by: Option<GroupBy>, output: &OutputArgs) -> String {
+    match group_by {
+        Some(group_by) => render_grouped(assets, show_encoded, group_by, output),
+        None => render_flat(assets, show_encoded, output),
+    }
+}
+
+fn asset_json(asset: &Asset, show_encoded: bool) -> serde_json::Value {
+    if show_encoded {
+        serde_json::json!({
+            "asset_id": asset.id,
+            "encoded_id": calculate_encoded_asset_id(&asset.id)
+        })
+    } else {
+        serde_json::json!(asset.id)
+    }
+}
+
+fn render_flat(assets: &[Asset], show_encoded: bool, output: &OutputArgs) -> String {
+    let asset_data: Vec<(&str, String)> = assets.iter()
+        .map(|asset| (
+            asset.id.as_str(),
+            if show_encoded { calculate_encoded_asset_id(&asset.id) } else { String::new() },
+        ))
+        .collect();
+
+    if output.json {
+        let json_data = serde_json::json!({
+            "assets": assets.iter().map(|asset| asset_json(asset, show_encoded)).collect::<Vec<_>>()
+        });
+        serde_json::to_string_pretty(&json_data).unwrap()
+    } else if output.csv {
+        let mut output = if show_encoded {
+            String::from("Asset ID, Encoded Asset ID\n")
+        } else {
+            String::from("Asset ID\n")
+        };
+        for (asset, encoded) in &asset_data {
+            if show_encoded {
+                output.push_str(&format!("{},{}\n", asset, encoded));
+            } else {
+                output.push_str(&format!("{}\n", asset));
+            }
+        }
+        output
+    } else if output.md {
+        let mut output = if show_encoded {
+            String::from("| Asset ID |  Encoded Asset ID |\n|----------|------------|\n")
+        } else {
+            String::from("| Asset ID |\n|----------|\n")
+        };
+        for (asset, encoded) in &asset_data {
+            if show_encoded {
+                output.push_str(&format!("| {} | {} |\n", asset, encoded));
+            } else {
+                output.push_str(&format!("| {} |\n", asset));
+            }
+        }
+        output
+    } else {
+        let mut output = String::from("Assets:\n\n");
+        for (asset, encoded) in &asset_data {
+            if show_encoded {
+                output.push_str(&format!("{}: {}\n", asset, encoded));
+            } else {
+                output.push_str(&format!("{}\n", asset));
+            }
+        }
+        output.push_str(&format!("\nTotal Assets: {}", assets.len()));
+        output
+    }
+}
+
+fn render_grouped(assets: &[Asset], show_encoded: bool, group_by: GroupBy, output: &OutputArgs) -> String {
+    let mut groups: BTreeMap<String, Vec<&Asset>> = BTreeMap::new();
+    for asset in assets {
+        groups.entry(group_by.key(asset)).or_default().push(asset);
+    }
+
+    if output.json {
+        let json_data = serde_json::json!({
+            "groups": groups.iter()
+                .map(|(group, members)| serde_json::json!({
+                    "group": group,
+                    "assets": members.iter().map(|asset| asset_json(asset, show_encoded)).collect::<Vec<_>>()
+                }))
+                .collect::<Vec<_>>()
+        });
+        return serde_json::to_string_pretty(&json_data).unwrap();
+    }
+
+    if output.csv || output.md {
+        let mut headers = vec![group_by.label(), "Asset ID"];
+        if show_encoded {
+            headers.push("Encoded Asset ID");
+        }
+        let rows: Vec<Vec<String>> = groups.iter()
+            .flat_map(|(group, members)| members.iter().map(move |asset| {
+                let mut row = vec![group.clone(), asset.id.clone()];
+                if show_encoded {
+                    row.push(calculate_encoded_asset_id(&asset.id));
+                }
+                row
+            }))
+            .collect();
+        return if output.csv { csv_table(&headers, &rows) } else { markdown_table(&headers, &rows) };
+    }
+
+    let mut output = String::new();
+    for (group, members) in &groups {
+        output.push_str(&format!("{} ({}):\n", group, members.len()));
+        for asset in members {
+            if show_encoded {
+                output.push_str(&format!("  {}: {}\n", asset.id, calculate_encoded_asset_id(&asset.id)));
+            } else {
+                output.push_str(&format!("  {}\n", asset.id));
+            }
+        }
+        output.push('\n');
+    }
+    output.push_str(&format!("Total Assets: {} in {} groups", assets.len(), groups.len()));
+    output
+}
+
+/// Render prices in the format selected by the output flags
+pub fn render_prices(prices: &[PriceUpdate], output: &OutputArgs) -> String {
+    let now = Utc::now();
+
+    if output.json {
+        let json_data = serde_json::json!({
+            "prices": prices.iter()
+                .map(|price| {
+                    serde_json::json!({
+                        "asset_id": price.asset_id,
+                        "price": format_quantized_price(&price.price),
+                        "quantized_price": price.price,
+                        "timestamp": price.timestamp,
+                        "age_seconds": age_seconds(price.timestamp, now),
+                        "encoded_asset_id": calculate_encoded_asset_id(&price.asset_id),
+                    })
+                })
+                .collect::<Vec<_>>()
+        });
+        return serde_json::to_string_pretty(&json_data).unwrap();
+    }
+
+    if output.csv || output.md {
+        let headers = ["Asset ID", "Price", "Quantized Price", "Timestamp", "Age", "Encoded Asset ID"];
+        let rows: Vec<Vec<String>> = prices.iter()
+            .map(|price| vec![
+                price.asset_id.clone(),
+                format_quantized_price(&price.price),
+                price.price.clone(),
+                format_timestamp(price.timestamp),
+                format_age(age_seconds(price.timestamp, now)),
+                calculate_encoded_asset_id(&price.asset_id),
+            ])
+            .collect();
+        return if output.csv {
+            csv_table(&headers, &rows)
+        } else {
+            markdown_table(&headers, &rows)
+        };
+    }
+
+    let mut text = String::new();
+    for price in prices {
+        text.push_str(&format!("{}\n", price.asset_id));
+        text.push_str(&format!("  price:      {}\n", format_quantized_price(&price.price)));
+        text.push_str(&format!("  quantized:  {}\n", price.price));
+        text.push_str(&format!("  timestamp:  {} ({} ago)\n", format_timestamp(price.timestamp), format_age(age_seconds(price.timestamp, now))));
+        text.push_str(&format!("  encoded:    {}\n\n", calculate_encoded_asset_id(&price.asset_id)));
+    }
+    text.trim_end().to_string()
+}
+
+/// Render a config diff for the terminal
+pub fn render_diff(diff: &ConfigDiff, removed_label: &str) -> String {
+    let mut output = String::new();
+    for asset in &diff.added {
+        output.push_str(&format!("{}\n", format!("+ {}", asset).green()));
+    }
+    for asset in &diff.removed {
+        output.push_str(&format!("{} ({})\n", format!("- {}", asset).red(), removed_label));
+    }
+    for change in &diff.changed {
+        output.push_str(&format!("{}  {}\n", format!("~ {}", change.asset_id).yellow(), change.describe()));
+    }
+    if diff.is_empty() {
+        output.push_str("No differences\n");
+    }
+    match diff.unconfigured_live {
+        Some(count) => output.push_str(&format!(
+            "\n{} {}; {} live assets not in config",
+            diff.removed.len(),
+            removed_label,
+            count,
+        )),
+        None => output.push_str(&format!(
+            "\n{} added, {} {}, {} changed",
+            diff.added.len(),
+            diff.removed.len(),
+            removed_label,
+            diff.changed.len(),
+        )),
+    }
+    output
+}
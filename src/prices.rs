@@ -0,0 +1,62 @@
+//! Latest prices and helpers for formatting them.
+
+use chrono::{DateTime, Utc};
+use serde_derive::Deserialize;
+
+/// Number of decimal places in Stork quantized prices
+pub const PRICE_DECIMALS: usize = 18;
+
+/// Latest price for one asset as returned by `/v1/prices/latest`
+#[derive(Deserialize, Clone)]
+pub struct PriceUpdate {
+    /// The asset the price is for
+    pub asset_id: String,
+    /// Nanoseconds since the Unix epoch
+    pub timestamp: u64,
+    /// Price as an integer scaled by 10^18
+    pub price: String,
+}
+
+/// Convert a quantized price into a decimal string, e.g. "67900500000000000000000" -> "67900.5"
+pub fn format_quantized_price(quantized: &str) -> String {
+    let (sign, digits) = match quantized.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", quantized),
+    };
+    let digits = format!("{:0>width$}", digits, width = PRICE_DECIMALS + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - PRICE_DECIMALS);
+    let fraction = fraction.trim_end_matches('0');
+
+    if fraction.is_empty() {
+        format!("{}{}", sign, whole)
+    } else {
+        format!("{}{}.{}", sign, whole, fraction)
+    }
+}
+
+/// Format a nanosecond timestamp as a UTC date and time
+pub fn format_timestamp(timestamp_ns: u64) -> String {
+    DateTime::from_timestamp_nanos(timestamp_ns as i64)
+        .format("%Y-%m-%d %H:%M:%S%.3f UTC")
+        .to_string()
+}
+
+/// Seconds elapsed between a nanosecond timestamp and `now`
+pub fn age_seconds(timestamp_ns: u64, now: DateTime<Utc>) -> f64 {
+    let now_ns = now.timestamp_nanos_opt().unwrap_or(i64::MAX) as i128;
+    (now_ns - timestamp_ns as i128) as f64 / 1e9
+}
+
+/// Human friendly age, e.g. "850ms", "12.3s", "4m 5s". Clock skew never shows a negative age.
+pub fn format_age(seconds: f64) -> String {
+    let seconds = seconds.max(0.0);
+    if seconds < 1.0 {
+        format!("{}ms", (seconds * 1000.0).round() as u64)
+    } else if seconds < 60.0 {
+        format!("{:.1}s", seconds)
+    } else if seconds < 3600.0 {
+        format!("{}m {}s", seconds as u64 / 60, seconds as u64 % 60)
+    } else {
+        format!("{}h {}m", seconds as u64 / 3600, seconds as u64 % 3600 / 60)
+    }
+}
@@ -0,0 +1,114 @@
+//! Filtering and weighting the pool random assets are picked from.
+
+use glob::{MatchOptions, Pattern};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use regex::Regex;
+
+use crate::asset::Asset;
+
+/// Base assets boosted by `--prefer-majors`
+const MAJORS: &[&str] = &[
+    "BTC", "ETH", "SOL", "BNB", "XRP", "ADA", "DOGE", "AVAX", "DOT", "LINK",
+    "LTC", "TRX", "TON", "BCH", "ATOM", "UNI", "NEAR", "SUI", "APT", "ARB", "OP",
+];
+
+/// How much more likely a major is to be picked than any other asset
+const MAJOR_WEIGHT: f64 = 10.0;
+
+/// Narrows and weights the pool random assets are picked from
+#[derive(Default)]
+pub struct RandomFilter {
+    quote: Option<String>,
+    base_regex: Option<Regex>,
+    exclude: Vec<Pattern>,
+    prefer_majors: bool,
+}
+
+impl RandomFilter {
+    /// Filter built from the `gen-config` options. `base_regex` is matched against the base asset;
+    /// `exclude` is a comma-separated list of asset ID globs.
+    pub fn new(quote: Option<&str>, base_regex: Option<&str>, exclude: Option<&str>, prefer_majors: bool) -> Result<Self, String> {
+        let base_regex = base_regex
+            .map(|regex| Regex::new(regex).map_err(|e| format!("Invalid base regex '{}': {}", regex, e)))
+            .transpose()?;
+        let exclude = exclude
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .map(|pattern| Pattern::new(pattern).map_err(|e| format!("Invalid exclude pattern '{}': {}", pattern, e)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            quote: quote.map(|quote| quote.trim().to_uppercase()),
+            base_regex,
+            exclude,
+            prefer_majors,
+        })
+    }
+
+    fn parse(&self, asset_id: &str) -> Asset {
+        // Let --quote also match quote currencies missing from the known table
+        self.quote.as_deref()
+            .and_then(|quote| Asset::parse_with_quote(asset_id, quote))
+            .unwrap_or_else(|| Asset::parse(asset_id))
+    }
+
+    /// Whether an asset may be picked
+    pub fn matches(&self, asset_id: &str) -> bool {
+        let asset = self.parse(asset_id);
+        if let Some(wanted) = &self.quote {
+            if asset.quote.as_ref() != Some(wanted) {
+                return false;
+            }
+        }
+        if let Some(regex) = &self.base_regex {
+            if !regex.is_match(&asset.base) {
+                return false;
+            }
+        }
+        let options = MatchOptions { case_sensitive: false, ..MatchOptions::new() };
+        !self.exclude.iter().any(|pattern| pattern.matches_with(asset_id, options))
+    }
+
+    /// Relative chance of an asset being picked
+    fn weight(&self, asset_id: &str) -> f64 {
+        if self.prefer_majors && MAJORS.contains(&self.parse(asset_id).base.as_str()) {
+            MAJOR_WEIGHT
+        } else {
+            1.0
+        }
+    }
+
+    /// The filter as command line flags, for recording alongside the seed
+    pub fn describe(&self) -> String {
+        let mut flags = Vec::new();
+        if let Some(quote) = &self.quote {
+            flags.push(format!("--quote {}", quote));
+        }
+        if let Some(regex) = &self.base_regex {
+            flags.push(format!("--base-regex '{}'", regex.as_str()));
+        }
+        if !self.exclude.is_empty() {
+            let patterns: Vec<&str> = self.exclude.iter().map(|pattern| pattern.as_str()).collect();
+            flags.push(format!("--exclude '{}'", patterns.join(",")));
+        }
+        if self.prefer_majors {
+            flags.push("--prefer-majors".to_string());
+        }
+        flags.join(" ")
+    }
+
+    /// Pick up to `n` assets from a pool already narrowed with [`RandomFilter::matches`]
+    pub fn choose<R: Rng>(&self, pool: &[String], n: usize, rng: &mut R) -> Vec<String> {
+        let n = n.min(pool.len());
+        if self.prefer_majors {
+            pool.choose_multiple_weighted(rng, n, |asset| self.weight(asset))
+                .map(|chosen| chosen.cloned().collect())
+                .unwrap_or_default()
+        } else {
+            pool.choose_multiple(rng, n).cloned().collect()
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use chrono::{DateTime, SecondsFormat, SubsecRound, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use stork_asset::diff::ConfigDiff;
+
+use crate::auth::CONFIG_DIR_NAME;
+use crate::cache::url_slug;
+
+/// The asset list at a point in time, as stored on disk
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub base_url: String,
+    pub taken_at: DateTime<Utc>,
+    /// Sorted asset IDs
+    pub assets: Vec<String>,
+}
+
+impl Snapshot {
+    pub fn new(base_url: &str, mut assets: Vec<String>) -> Self {
+        assets.sort();
+        Self { base_url: base_url.to_string(), taken_at: Utc::now().trunc_subsecs(0), assets }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    /// When the snapshot was taken, e.g. `2026-10-16T12:00:00Z`
+    pub fn taken_at_rfc3339(&self) -> String {
+        self.taken_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+
+    /// Save the snapshot in `dir`, named after the time it was taken
+    pub fn save(&self, dir: &Path) -> Result<PathBuf, String> {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let path = dir.join(format!("{}.json", self.taken_at.format("%Y%m%dT%H%M%SZ")));
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize snapshot: {}", e))?;
+        fs::write(&path, contents)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+/// Default snapshot directory for a REST API, e.g. `~/.local/share/stork-asset/snapshots/rest.jp.stork-oracle.network`
+pub fn default_dir(base_url: &str) -> Result<PathBuf, String> {
+    dirs::data_dir()
+        .map(|dir| dir.join(CONFIG_DIR_NAME).join("snapshots").join(url_slug(base_url)))
+        .ok_or_else(|| "Unable to determine data directory".to_string())
+}
+
+/// Snapshot files in `dir`, oldest first
+pub fn list(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    // File names are UTC timestamps, so they sort chronologically
+    paths.sort();
+    Ok(paths)
+}
+
+/// Find a snapshot by `latest`, `previous`, a timestamp file name (with or without `.json`) or a path
+pub fn resolve(dir: &Path, reference: &str) -> Result<PathBuf, String> {
+    let snapshots = list(dir)?;
+    let from_end = match reference {
+        "latest" => Some(1),
+        "previous" => Some(2),
+        _ => None,
+    };
+    if let Some(n) = from_end {
+        return snapshots.len().checked_sub(n)
+            .map(|index| snapshots[index].clone())
+            .ok_or_else(|| format!("No {} snapshot in {} (found {})", reference, dir.display(), snapshots.len()));
+    }
+
+    let path = Path::new(reference);
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+    let stem = reference.trim_end_matches(".json");
+    snapshots.into_iter()
+        .find(|path| path.file_stem().is_some_and(|name| name == stem))
+        .ok_or_else(|| format!("Snapshot '{}' not found in {}", reference, dir.display()))
+}
+
+/// Assets added and removed between two snapshots
+pub fn compare(from: &Snapshot, to: &Snapshot) -> ConfigDiff {
+    ConfigDiff {
+        added: to.assets.iter().filter(|asset| !from.assets.contains(asset)).cloned().collect(),
+        removed: from.assets.iter().filter(|asset| !to.assets.contains(asset)).cloned().collect(),
+        ..ConfigDiff::default()
+    }
+}
+
+/// Render the changes as JSON, for posting from scripts
+pub fn render_json(from: &Snapshot, to: &Snapshot, diff: &ConfigDiff) -> String {
+    let json_data = serde_json::json!({
+        "base_url": to.base_url,
+        "from": from.taken_at_rfc3339(),
+        "to": to.taken_at_rfc3339(),
+        "added": diff.added,
+        "removed": diff.removed,
+    });
+    serde_json::to_string_pretty(&json_data).unwrap()
+}
@@ -0,0 +1,346 @@
+use chrono::Utc;
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+use stork_asset::prices::{age_seconds, format_age, format_quantized_price, format_timestamp, PriceUpdate};
+use tungstenite::client::IntoClientRequest;
+use tungstenite::http::header::AUTHORIZATION;
+use tungstenite::http::{HeaderValue, StatusCode};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+const SUBSCRIBE_PATH: &str = "/evm/subscribe";
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+/// How often a blocked read wakes up to check the stop conditions
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+/// Settings for a `subscribe` session
+pub struct SubscribeOptions {
+    pub url: String,
+    pub token: Option<String>,
+    pub assets: Vec<String>,
+    pub ndjson: bool,
+    pub reconnect: bool,
+    pub duration: Option<Duration>,
+    pub count: Option<usize>,
+}
+
+/// Why a `subscribe` session failed
+#[derive(Debug)]
+pub enum SubscribeError {
+    /// The URL or token can't be used to connect at all
+    Invalid(String),
+    /// The connection failed or dropped; worth reconnecting
+    Transport(String),
+    /// The server rejected the auth token
+    Auth(String),
+    /// The server rejected the subscription, e.g. for an unknown asset
+    Rejected(String),
+}
+
+impl fmt::Display for SubscribeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubscribeError::Invalid(e) | SubscribeError::Transport(e) => f.write_str(e),
+            SubscribeError::Auth(e) | SubscribeError::Rejected(e) => write!(f, "Server error: {}", e),
+        }
+    }
+}
+
+impl SubscribeError {
+    /// Server `error` messages don't say what kind of error they are, so look for auth wording
+    fn from_server(error: String) -> Self {
+        let lower = error.to_lowercase();
+        if ["auth", "token", "unauthorized", "forbidden"].iter().any(|word| lower.contains(word)) {
+            SubscribeError::Auth(error)
+        } else {
+            SubscribeError::Rejected(error)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ServerMessage {
+    #[serde(rename = "type")]
+    message_type: String,
+    #[serde(default)]
+    data: serde_json::Value,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Derive the WebSocket endpoint from a REST base URL,
+/// e.g. `https://rest.jp.stork-oracle.network` -> `wss://api.jp.stork-oracle.network/evm/subscribe`
+pub fn websocket_url(base_url: &str) -> String {
+    let base_url = base_url.trim_end_matches('/');
+    let (scheme, host) = if let Some(host) = base_url.strip_prefix("https://") {
+        ("wss", host)
+    } else if let Some(host) = base_url.strip_prefix("http://") {
+        ("ws", host)
+    } else {
+        ("wss", base_url)
+    };
+    let host = match host.strip_prefix("rest.") {
+        Some(rest) => format!("api.{}", rest),
+        None => host.to_string(),
+    };
+    format!("{}://{}{}", scheme, host, SUBSCRIBE_PATH)
+}
+
+/// Tracks when the session should end
+struct StopCondition {
+    deadline: Option<Instant>,
+    remaining: Option<usize>,
+}
+
+impl StopCondition {
+    fn done(&self) -> bool {
+        self.remaining == Some(0) || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    fn time_left(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+}
+
+/// Stream price updates until the duration or count limit is reached, or forever if neither is set.
+/// Dropped connections are retried; errors reported by the server end the session.
+pub fn run(options: &SubscribeOptions) -> Result<(), SubscribeError> {
+    let mut stop = StopCondition {
+        deadline: options.duration.map(|duration| Instant::now() + duration),
+        remaining: options.count,
+    };
+    let mut reconnect_delay = Duration::from_secs(1);
+
+    loop {
+        let result = connect(options).and_then(|mut socket| {
+            reconnect_delay = Duration::from_secs(1);
+            let result = stream(&mut socket, options, &mut stop);
+            let _ = socket.close(None);
+            let _ = socket.flush();
+            result
+        });
+
+        if stop.done() {
+            return Ok(());
+        }
+        let error = match result {
+            Ok(()) => SubscribeError::Transport("Connection closed by server".to_string()),
+            Err(e) => e,
+        };
+        if !options.reconnect || !matches!(error, SubscribeError::Transport(_)) {
+            return Err(error);
+        }
+
+        let delay = match stop.time_left() {
+            Some(left) => reconnect_delay.min(left),
+            None => reconnect_delay,
+        };
+        eprintln!("{}. Reconnecting in {}s...", error, delay.as_secs_f64().round());
+        thread::sleep(delay);
+        reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+
+        if stop.done() {
+            return Ok(());
+        }
+    }
+}
+
+fn connect(options: &SubscribeOptions) -> Result<Socket, SubscribeError> {
+    let mut request = options.url.as_str()
+        .into_client_request()
+        .map_err(|e| SubscribeError::Invalid(format!("Invalid WebSocket URL {}: {}", options.url, e)))?;
+    if let Some(token) = &options.token {
+        let value = HeaderValue::from_str(&format!("Basic {}", token))
+            .map_err(|_| SubscribeError::Invalid("Auth token contains invalid characters".to_string()))?;
+        request.headers_mut().insert(AUTHORIZATION, value);
+    }
+
+    let (mut socket, _) = tungstenite::connect(request).map_err(|e| match &e {
+        tungstenite::Error::Http(response)
+            if matches!(response.status(), StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) =>
+        {
+            SubscribeError::Auth(format!("{} rejected the auth token ({})", options.url, response.status()))
+        }
+        _ => SubscribeError::Transport(format!("Failed to connect to {}: {}", options.url, e)),
+    })?;
+    set_read_timeout(&socket, POLL_INTERVAL).map_err(SubscribeError::Transport)?;
+
+    let subscribe = serde_json::json!({
+        "type": "subscribe",
+        "data": options.assets,
+    });
+    socket.send(Message::text(subscribe.to_string()))
+        .map_err(|e| SubscribeError::Transport(format!("Failed to send subscribe message: {}", e)))?;
+
+    if !options.ndjson {
+        eprintln!("Subscribed to {} on {}", options.assets.join(", "), options.url);
+    }
+    Ok(socket)
+}
+
+fn set_read_timeout(socket: &Socket, timeout: Duration) -> Result<(), String> {
+    let stream = match socket.get_ref() {
+        MaybeTlsStream::Plain(stream) => stream,
+        MaybeTlsStream::NativeTls(stream) => stream.get_ref(),
+        _ => return Ok(()),
+    };
+    stream.set_read_timeout(Some(timeout))
+        .map_err(|e| format!("Failed to configure socket: {}", e))
+}
+
+/// Read messages from an open connection until it closes or the stop condition is met
+fn stream(socket: &mut Socket, options: &SubscribeOptions, stop: &mut StopCondition) -> Result<(), SubscribeError> {
+    while !stop.done() {
+        let message = match socket.read() {
+            Ok(message) => message,
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(e) => return Err(SubscribeError::Transport(format!("Connection error: {}", e))),
+        };
+
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => return Ok(()),
+            _ => continue,
+        };
+
+        let message: ServerMessage = match serde_json::from_str(text.as_str()) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("Warning: Ignoring unrecognized message: {}", e);
+                continue;
+            }
+        };
+
+        match message.message_type.as_str() {
+            "oracle_prices" => {
+                let updates: BTreeMap<String, PriceUpdate> = serde_json::from_value(message.data)
+                    .map_err(|e| SubscribeError::Transport(format!("Invalid oracle_prices message: {}", e)))?;
+                for update in updates.values() {
+                    print_update(update, options.ndjson);
+                    if let Some(remaining) = stop.remaining.as_mut() {
+                        *remaining = remaining.saturating_sub(1);
+                        if *remaining == 0 {
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            "error" => {
+                let error = message.error
+                    .or_else(|| message.data.as_str().map(String::from))
+                    .unwrap_or_else(|| "unknown error".to_string());
+                return Err(SubscribeError::from_server(error));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn print_update(update: &PriceUpdate, ndjson: bool) {
+    let now = Utc::now();
+    if ndjson {
+        let line = serde_json::json!({
+            "asset_id": update.asset_id,
+            "price": format_quantized_price(&update.price),
+            "quantized_price": update.price,
+            "timestamp": update.timestamp,
+            "received_at": now.timestamp_nanos_opt(),
+        });
+        println!("{}", line);
+    } else {
+        println!(
+            "{}  {:<12} {:>24}  ({} old)",
+            format_timestamp(update.timestamp),
+            update.asset_id,
+            format_quantized_price(&update.price),
+            format_age(age_seconds(update.timestamp, now)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    /// Accept a single connection on a loopback port and send `replies` once the client has
+    /// subscribed. Returns the URL to connect to; later connections are refused.
+    fn serve_once(replies: Vec<serde_json::Value>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}{}", listener.local_addr().unwrap(), SUBSCRIBE_PATH);
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            let subscribe = socket.read().unwrap();
+            assert!(subscribe.to_text().unwrap().contains("\"subscribe\""));
+            for reply in replies {
+                socket.send(Message::text(reply.to_string())).unwrap();
+            }
+            // Keep the connection open until the client leaves
+            while socket.read().is_ok() {}
+        });
+        url
+    }
+
+    fn price(asset_id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "type": "oracle_prices",
+            "data": { asset_id: { "asset_id": asset_id, "timestamp": 1, "price": "1000000000000000000" } },
+        })
+    }
+
+    fn options(url: String, count: Option<usize>) -> SubscribeOptions {
+        SubscribeOptions {
+            url,
+            token: None,
+            assets: vec!["BTCUSD".to_string()],
+            ndjson: true,
+            reconnect: true,
+            // Long enough to tell a session ended by the count or an error from one that timed out
+            duration: Some(Duration::from_secs(10)),
+            count,
+        }
+    }
+
+    #[test]
+    fn stops_after_count_updates() {
+        let url = serve_once(vec![price("BTCUSD"), price("BTCUSD"), price("BTCUSD")]);
+        let started = Instant::now();
+        assert!(run(&options(url, Some(2))).is_ok());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn server_error_is_fatal() {
+        let url = serve_once(vec![serde_json::json!({ "type": "error", "error": "invalid asset: NOPEUSD" })]);
+        let started = Instant::now();
+        let error = run(&options(url, None)).unwrap_err();
+        assert!(matches!(error, SubscribeError::Rejected(ref e) if e.contains("NOPEUSD")), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn rejected_token_is_fatal() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}{}", listener.local_addr().unwrap(), SUBSCRIBE_PATH);
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request);
+            let _ = stream.write_all(b"HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\n\r\n");
+        });
+
+        let error = run(&options(url, None)).unwrap_err();
+        assert!(matches!(error, SubscribeError::Auth(_)), "{}", error);
+    }
+}
@@ -0,0 +1,469 @@
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use ed25519_dalek::{Signature, VerifyingKey};
+use reqwest::StatusCode;
+use semver::Version;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use stork_asset::http::{self, HttpError, HttpSettings};
+use stork_asset::VERSION;
+
+use crate::cache::{self, now};
+
+const RELEASES_URL: &str = "https://api.github.com/repos/henrymbaldwin/stork-asset-cli/releases";
+/// Releases fetched when looking for the latest one, newest first
+const RELEASES_PER_PAGE: usize = 30;
+
+/// Release asset listing the SHA-256 checksum of every binary, in `sha256sum` format
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+/// Ed25519 signature of the checksum file
+const SIGNATURE_ASSET: &str = "SHA256SUMS.sig";
+
+/// Ed25519 public key checksum files are signed with, as 64 hex digits. Release builds take it from
+/// the `STORK_ASSET_RELEASE_PUBLIC_KEY` environment variable; other builds can't verify updates.
+const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("STORK_ASSET_RELEASE_PUBLIC_KEY");
+
+/// Set to any value to turn off the background check for new releases
+pub const NO_UPDATE_CHECK_ENV_VAR: &str = "STORK_ASSET_NO_UPDATE_CHECK";
+/// How often to look for a new release, and to tell the user about one
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// The background check must never hold up a command for long
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long after starting a background check a command may wait for it before exiting
+const CHECK_WAIT: Duration = Duration::from_secs(1);
+
+/// The running background check: when it started, and a channel signalled once its result is saved
+static PENDING_CHECK: Mutex<Option<(Instant, Receiver<()>)>> = Mutex::new(None);
+
+/// Which releases the updater considers
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Channel {
+    /// Only full releases
+    Stable,
+    /// Pre-releases as well, e.g. 0.3.0-rc.1
+    Prerelease,
+}
+
+/// A GitHub release, as returned by the releases API
+#[derive(Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
+    pub published_at: Option<DateTime<Utc>>,
+    /// Release notes, in Markdown
+    pub body: Option<String>,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+/// A file attached to a release
+#[derive(Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    /// Version number without the leading `v`
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    /// Whether the release belongs to `channel`. A version with a pre-release tag is never stable.
+    pub fn in_channel(&self, channel: Channel) -> bool {
+        match channel {
+            Channel::Stable => !self.prerelease && Version::parse(self.version()).is_ok_and(|v| v.pre.is_empty()),
+            Channel::Prerelease => true,
+        }
+    }
+
+    pub fn semver(&self) -> Result<Version, String> {
+        Version::parse(self.version())
+            .map_err(|e| format!("Release {} doesn't have a valid version: {}", self.tag_name, e))
+    }
+
+    /// Whether the release has the signed checksum file `update` needs to verify it. Releases
+    /// published before signing was introduced don't.
+    pub fn is_signed(&self) -> bool {
+        self.asset(CHECKSUMS_ASSET).is_ok() && self.asset(SIGNATURE_ASSET).is_ok()
+    }
+
+    fn asset(&self, name: &str) -> Result<&ReleaseAsset, String> {
+        self.assets.iter()
+            .find(|asset| asset.name == name)
+            .ok_or_else(|| format!("Release {} has no {} asset", self.tag_name, name))
+    }
+}
+
+/// Recent published releases in `channel`, newest first
+pub fn list_releases(settings: &HttpSettings, channel: Channel) -> Result<Vec<Release>, String> {
+    let client = http::client(settings)?;
+    let request = client.get(RELEASES_URL).query(&[("per_page", RELEASES_PER_PAGE)]);
+    let releases: Vec<Release> = http::get_json(request, settings)?;
+    Ok(releases.into_iter()
+        .filter(|release| !release.draft && release.in_channel(channel))
+        .collect())
+}
+
+/// The highest version released in `channel`
+pub fn latest_release(settings: &HttpSettings, channel: Channel) -> Result<Release, String> {
+    list_releases(settings, channel)?
+        .into_iter()
+        .filter_map(|release| release.semver().ok().map(|version| (version, release)))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release)
+        .ok_or_else(|| "No releases found".to_string())
+}
+
+/// Release tag for a version, e.g. `0.2.0` -> `v0.2.0`
+pub fn tag_name(version: &str) -> String {
+    format!("v{}", version.trim_start_matches('v'))
+}
+
+/// The release tagged with `version`, e.g. `0.2.0` or `v0.2.0`. Returns `None` if there isn't one.
+pub fn find_release(settings: &HttpSettings, version: &str) -> Result<Option<Release>, String> {
+    let client = http::client(settings)?;
+    match http::get_json(client.get(format!("{}/tags/{}", RELEASES_URL, tag_name(version))), settings) {
+        Ok(release) => Ok(Some(release)),
+        Err(HttpError::Status(StatusCode::NOT_FOUND)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Version of the running executable
+pub fn current_version() -> Version {
+    Version::parse(VERSION).expect("the crate version is valid semver")
+}
+
+/// Name of the release asset built for this platform, e.g. `stork-asset-linux-amd64`
+pub fn platform_asset_name() -> Result<String, String> {
+    let os = match env::consts::OS {
+        "linux" => "linux",
+        "macos" => "macos",
+        "windows" => "windows",
+        other => return Err(format!("No release binaries are published for {}", other)),
+    };
+    let arch = match env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        other => return Err(format!("No release binaries are published for {}", other)),
+    };
+    Ok(format!("stork-asset-{}-{}", os, arch))
+}
+
+fn download(client: &reqwest::blocking::Client, asset: &ReleaseAsset, settings: &HttpSettings) -> Result<Vec<u8>, HttpError> {
+    http::send(client.get(&asset.browser_download_url), settings)?
+        .bytes()
+        .map(|bytes| bytes.to_vec())
+        .map_err(|e| HttpError::Network(format!("Failed to download {}: {}", asset.name, e)))
+}
+
+/// The release public key this build was made with
+fn release_key() -> Result<VerifyingKey, String> {
+    parse_release_key(RELEASE_PUBLIC_KEY)
+}
+
+/// Parse a built-in release public key. An empty one, as built when the release variable is unset,
+/// counts as none.
+fn parse_release_key(key: Option<&str>) -> Result<VerifyingKey, String> {
+    let key = key.map(str::trim).filter(|key| !key.is_empty()).ok_or(
+        "This build has no release public key to verify updates with. Install a release build, or update with `cargo install`",
+    )?;
+    let key_bytes: [u8; 32] = hex::decode(key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or("Invalid release public key")?;
+    VerifyingKey::from_bytes(&key_bytes).map_err(|e| format!("Invalid release public key: {}", e))
+}
+
+/// Check the checksum file was signed with the release key
+fn verify_signature(key: &VerifyingKey, checksums: &[u8], signature: &[u8]) -> Result<(), String> {
+    let signature = Signature::from_slice(signature)
+        .map_err(|_| format!("{} is not a valid Ed25519 signature", SIGNATURE_ASSET))?;
+    key.verify_strict(checksums, &signature)
+        .map_err(|_| format!("Signature check failed: {} was not signed with the release key", CHECKSUMS_ASSET))
+}
+
+/// Check `binary` against its line in the checksum file
+fn verify_checksum(checksums: &[u8], name: &str, binary: &[u8]) -> Result<(), String> {
+    let checksums = String::from_utf8_lossy(checksums);
+    let expected = checksums.lines()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(_, file)| file.trim().trim_start_matches('*') == name)
+        .map(|(hash, _)| hash.to_lowercase())
+        .ok_or_else(|| format!("{} has no checksum for {}", CHECKSUMS_ASSET, name))?;
+    let actual = hex::encode(Sha256::digest(binary));
+    if actual != expected {
+        return Err(format!("Checksum mismatch for {}: expected {}, got {}", name, expected, actual));
+    }
+    Ok(())
+}
+
+/// Download this platform's binary from `release` and check its checksum and signature
+pub fn download_verified(release: &Release, settings: &HttpSettings) -> Result<Vec<u8>, String> {
+    let key = release_key()?;
+    let name = platform_asset_name()?;
+    let binary_asset = release.asset(&name)?;
+    let checksums_asset = release.asset(CHECKSUMS_ASSET)?;
+    let signature_asset = release.asset(SIGNATURE_ASSET)?;
+
+    let client = http::client(settings)?;
+    let checksums = download(&client, checksums_asset, settings)?;
+    let signature = download(&client, signature_asset, settings)?;
+    verify_signature(&key, &checksums, &signature)?;
+
+    let binary = download(&client, binary_asset, settings)?;
+    verify_checksum(&checksums, &name, &binary)?;
+    Ok(binary)
+}
+
+/// Path of the running executable, with symlinks resolved
+pub fn current_exe() -> Result<PathBuf, String> {
+    env::current_exe()
+        .and_then(fs::canonicalize)
+        .map_err(|e| format!("Unable to locate the running executable: {}", e))
+}
+
+/// Whether the executable at `exe` can be replaced, i.e. files can be created next to it
+pub fn can_replace(exe: &Path) -> bool {
+    // A name unique to this process, so a probe file left behind by someone else can't be mistaken
+    // for one we were allowed to create
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or_default();
+    let probe = exe.with_file_name(format!(".stork-asset.write-test.{}.{}", process::id(), nanos));
+    match fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Whether `exe` is installed outside the user's home directory, so updating it likely needs root
+pub fn needs_root(exe: &Path) -> bool {
+    cfg!(unix) && !dirs::home_dir().is_some_and(|home| exe.starts_with(home))
+}
+
+/// Previous version kept by the last update, e.g. `/usr/local/bin/stork-asset.bak`
+pub fn backup_path(exe: &Path) -> PathBuf {
+    let mut name = exe.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    exe.with_file_name(name)
+}
+
+/// Move `new` into place as `exe`. On Unix the rename is atomic, so `exe` is never missing or half-written.
+fn swap_in(new: &Path, exe: &Path) -> io::Result<()> {
+    // A running executable can't be replaced on Windows, but it can be moved aside
+    #[cfg(windows)]
+    {
+        let old = exe.with_extension("old");
+        let _ = fs::remove_file(&old);
+        fs::rename(exe, &old)?;
+    }
+    fs::rename(new, exe)
+}
+
+/// Replace the executable at `exe` with `binary`, keeping the current one as a backup
+pub fn install(binary: &[u8], exe: &Path) -> Result<(), String> {
+    // Write next to the executable so the final rename stays on one filesystem
+    let staged = exe.with_file_name(".stork-asset.download");
+    let result = (|| {
+        fs::write(&staged, binary)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+        }
+        fs::copy(exe, backup_path(exe))?;
+        swap_in(&staged, exe)
+    })();
+    result.map_err(|e| {
+        let _ = fs::remove_file(&staged);
+        format!("Failed to replace {}: {}", exe.display(), e)
+    })
+}
+
+/// Restore the backup kept by the last update
+pub fn rollback(exe: &Path) -> Result<(), String> {
+    let backup = backup_path(exe);
+    if !backup.exists() {
+        return Err(format!("No previous version to roll back to ({} not found)", backup.display()));
+    }
+    swap_in(&backup, exe).map_err(|e| format!("Failed to restore {}: {}", backup.display(), e))
+}
+
+/// Result of the last background check for a new release, as stored on disk
+#[derive(Serialize, Deserialize, Default)]
+struct UpdateCheck {
+    /// Unix time in seconds
+    checked_at: u64,
+    latest_version: Option<String>,
+    /// When the user was last told about `latest_version`
+    notified_at: u64,
+}
+
+fn check_path() -> Result<PathBuf, String> {
+    Ok(cache::cache_dir()?.join("update-check.json"))
+}
+
+fn load_check() -> UpdateCheck {
+    check_path().ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_check(check: &UpdateCheck) -> Result<(), String> {
+    let path = check_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let contents = serde_json::to_string(check)
+        .map_err(|e| format!("Failed to serialize update check: {}", e))?;
+    fs::write(&path, contents)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Waits briefly for the background check to be recorded when dropped, see [`finish_background_check`]
+pub struct BackgroundCheck;
+
+impl Drop for BackgroundCheck {
+    fn drop(&mut self) {
+        finish_background_check();
+    }
+}
+
+/// Tell the user about a newer release found by an earlier check, at most once a day, and start
+/// a new check in the background if the last one is over a day old. Keep the returned guard
+/// alive until the command is done. Does nothing when stderr isn't a terminal.
+pub fn background_check(settings: &HttpSettings) -> Option<BackgroundCheck> {
+    if env::var_os(NO_UPDATE_CHECK_ENV_VAR).is_some() || !io::stderr().is_terminal() {
+        return None;
+    }
+
+    let mut check = load_check();
+    let now = now();
+    let newer = check.latest_version.as_deref()
+        .and_then(|version| Version::parse(version).ok())
+        .filter(|latest| *latest > current_version());
+    if let Some(latest) = newer {
+        if now.saturating_sub(check.notified_at) >= CHECK_INTERVAL.as_secs() {
+            eprintln!("A new version of stork-asset is available: {} -> {}", VERSION, latest);
+            eprintln!("Run `stork-asset update` to install it\n");
+            check.notified_at = now;
+            let _ = save_check(&check);
+        }
+    }
+
+    if now.saturating_sub(check.checked_at) < CHECK_INTERVAL.as_secs() {
+        return None;
+    }
+    // Record the attempt up front, so a check that fails or is cut short is only retried a day later
+    check.checked_at = now;
+    let _ = save_check(&check);
+
+    let settings = HttpSettings { timeout: CHECK_TIMEOUT, connect_timeout: CHECK_TIMEOUT, retries: 0, ..settings.clone() };
+    let (done, pending) = mpsc::channel();
+    thread::spawn(move || {
+        if let Ok(release) = latest_release(&settings, Channel::Stable) {
+            check.latest_version = Some(release.version().to_string());
+            let _ = save_check(&check);
+        }
+        let _ = done.send(());
+    });
+    if let Ok(mut pending_check) = PENDING_CHECK.lock() {
+        *pending_check = Some((Instant::now(), pending));
+    }
+    Some(BackgroundCheck)
+}
+
+/// Give a running background check until [`CHECK_WAIT`] after it started to save its result.
+/// Called before exiting, whether the command succeeded or not.
+pub fn finish_background_check() {
+    let pending = PENDING_CHECK.lock().ok().and_then(|mut pending_check| pending_check.take());
+    if let Some((started, done)) = pending {
+        let _ = done.recv_timeout((started + CHECK_WAIT).saturating_duration_since(Instant::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const BINARY: &[u8] = b"stork-asset binary";
+
+    fn checksums() -> Vec<u8> {
+        format!(
+            "{}  stork-asset-linux-amd64\n{}  stork-asset-macos-arm64\n",
+            hex::encode(Sha256::digest(BINARY)),
+            hex::encode(Sha256::digest(b"other binary")),
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn checksum_matches() {
+        assert!(verify_checksum(&checksums(), "stork-asset-linux-amd64", BINARY).is_ok());
+    }
+
+    #[test]
+    fn checksum_mismatch_is_rejected() {
+        let err = verify_checksum(&checksums(), "stork-asset-linux-amd64", b"tampered").unwrap_err();
+        assert!(err.contains("Checksum mismatch"), "{}", err);
+    }
+
+    #[test]
+    fn missing_checksum_line_is_rejected() {
+        let err = verify_checksum(&checksums(), "stork-asset-windows-amd64", BINARY).unwrap_err();
+        assert!(err.contains("no checksum for stork-asset-windows-amd64"), "{}", err);
+    }
+
+    #[test]
+    fn signature_from_release_key_is_accepted() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let signature = signing_key.sign(&checksums());
+        assert!(verify_signature(&signing_key.verifying_key(), &checksums(), &signature.to_bytes()).is_ok());
+    }
+
+    #[test]
+    fn bad_signature_is_rejected() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let other_key = SigningKey::from_bytes(&[8; 32]);
+        let checksums = checksums();
+
+        let wrong_key = other_key.sign(&checksums).to_bytes();
+        assert!(verify_signature(&signing_key.verifying_key(), &checksums, &wrong_key).is_err());
+
+        let mut tampered = checksums.clone();
+        tampered[0] ^= 1;
+        let signature = signing_key.sign(&checksums).to_bytes();
+        assert!(verify_signature(&signing_key.verifying_key(), &tampered, &signature).is_err());
+
+        assert!(verify_signature(&signing_key.verifying_key(), &checksums, b"not a signature").is_err());
+    }
+
+    #[test]
+    fn empty_release_key_counts_as_none() {
+        for key in [None, Some(""), Some("  \n")] {
+            let err = parse_release_key(key).err().unwrap();
+            assert!(err.contains("no release public key"), "{}", err);
+        }
+        let key = hex::encode(SigningKey::from_bytes(&[7; 32]).verifying_key().to_bytes());
+        assert!(parse_release_key(Some(&format!("{}\n", key))).is_ok());
+        assert_eq!(parse_release_key(Some("abcd")).err().unwrap(), "Invalid release public key");
+    }
+}
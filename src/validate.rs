@@ -0,0 +1,99 @@
+//! Checking an asset config for mistakes.
+
+use crate::encode::calculate_encoded_asset_id;
+use crate::config::Config;
+
+/// How serious an [`Issue`] is. Errors make a config invalid; warnings only with `--strict`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// The config is invalid
+    Error,
+    /// Likely a mistake, but the config can be used
+    Warning,
+}
+
+impl Severity {
+    /// Lowercase name used in output, e.g. "error"
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// A single problem found in a config file
+pub struct Issue {
+    /// Whether the issue makes the config invalid
+    pub severity: Severity,
+    /// Map key of the asset the issue is about, if any
+    pub asset: Option<String>,
+    /// Stable identifier for scripts, e.g. "encoded-id-mismatch"
+    pub code: &'static str,
+    /// Description of the issue
+    pub message: String,
+}
+
+impl Issue {
+    fn error(asset: &str, code: &'static str, message: String) -> Self {
+        Self { severity: Severity::Error, asset: Some(asset.to_string()), code, message }
+    }
+
+    fn warning(asset: &str, code: &'static str, message: String) -> Self {
+        Self { severity: Severity::Warning, asset: Some(asset.to_string()), code, message }
+    }
+}
+
+/// Check a config for inconsistencies and suspicious settings.
+/// `available` is the live asset list; pass `None` to skip the availability check.
+pub fn validate_config(config: &Config, available: Option<&[String]>) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    if config.assets.is_empty() {
+        issues.push(Issue {
+            severity: Severity::Warning,
+            asset: None,
+            code: "empty-config",
+            message: "Config contains no assets".to_string(),
+        });
+    }
+
+    for (key, asset) in &config.assets {
+        if key != &asset.asset_id {
+            issues.push(Issue::error(key, "key-mismatch",
+                format!("Map key '{}' does not match asset_id '{}'", key, asset.asset_id)));
+        }
+
+        let expected = calculate_encoded_asset_id(&asset.asset_id);
+        if !asset.encoded_asset_id.eq_ignore_ascii_case(&expected) {
+            issues.push(Issue::error(key, "encoded-id-mismatch",
+                format!("encoded_asset_id is {}, but {} encodes to {}", asset.encoded_asset_id, asset.asset_id, expected)));
+        }
+
+        if let Some(available) = available {
+            if !available.contains(&asset.asset_id) {
+                issues.push(Issue::error(key, "unavailable",
+                    format!("{} is not listed by the Stork REST API", asset.asset_id)));
+            }
+        }
+
+        let percent = asset.percent_change_threshold;
+        if !percent.is_finite() || percent < 0.0 {
+            issues.push(Issue::error(key, "invalid-threshold",
+                format!("percent_change_threshold {} must be a non-negative number", percent)));
+        } else if percent == 0.0 {
+            issues.push(Issue::warning(key, "zero-threshold",
+                "percent_change_threshold is 0, so every price change triggers an update".to_string()));
+        } else if percent > 100.0 {
+            issues.push(Issue::warning(key, "large-threshold",
+                format!("percent_change_threshold {} is over 100 percent", percent)));
+        }
+
+        if asset.fallback_period_sec == 0 {
+            issues.push(Issue::warning(key, "zero-fallback",
+                "fallback_period_sec is 0, so updates are pushed continuously".to_string()));
+        }
+    }
+
+    issues
+}
@@ -0,0 +1,464 @@
+//! Verifying Stork signed prices against Stork's public key.
+
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::encode::calculate_encoded_asset_id;
+
+/// Signature portion of a Stork signed price
+#[derive(Deserialize)]
+pub struct EvmSignature {
+    /// `r` as 32 hex-encoded bytes
+    pub r: String,
+    /// `s` as 32 hex-encoded bytes
+    pub s: String,
+    /// Recovery ID as a hex-encoded byte, `0x1b` or `0x1c`
+    pub v: String,
+}
+
+/// A signature and the time it was made
+#[derive(Deserialize)]
+pub struct TimestampedSignature {
+    /// Signature over the price message
+    pub signature: EvmSignature,
+    /// Nanoseconds since the Unix epoch
+    pub timestamp: u64,
+    /// Keccak-256 hash of the signed message, as published
+    #[serde(default)]
+    pub msg_hash: Option<String>,
+}
+
+/// The aggregation algorithm the price was calculated with
+#[derive(Deserialize)]
+pub struct CalculationAlg {
+    /// Hex-encoded checksum identifying the algorithm, part of the signed message
+    pub checksum: String,
+}
+
+/// The `stork_signed_price` object published with every Stork price
+#[derive(Deserialize)]
+pub struct StorkSignedPrice {
+    /// EVM address of the Stork key that signed the price
+    pub public_key: String,
+    /// Keccak-256 hash of the asset ID
+    pub encoded_asset_id: String,
+    /// Price as an integer scaled by 10^18
+    pub price: String,
+    /// Signature over the other fields
+    pub timestamped_signature: TimestampedSignature,
+    /// Merkle root of the publisher prices the price was aggregated from
+    pub publisher_merkle_root: String,
+    /// Algorithm the price was aggregated with
+    pub calculation_alg: CalculationAlg,
+}
+
+/// A signed price together with the asset ID it claims to be for, if known
+pub struct SignedPriceEntry {
+    /// Asset ID the price is for, checked against its encoded asset ID
+    pub asset_id: Option<String>,
+    /// The signed price itself
+    pub signed_price: StorkSignedPrice,
+}
+
+/// Outcome of a single check
+pub struct Check {
+    /// Short name shown in reports, e.g. "signature"
+    pub name: &'static str,
+    /// Whether the check passed
+    pub passed: bool,
+    /// What was found, or why the check failed
+    pub detail: String,
+}
+
+/// Verification result for one signed price
+pub struct VerifyReport {
+    /// Asset ID the price is for, if known
+    pub asset_id: Option<String>,
+    /// Every check run, in order
+    pub checks: Vec<Check>,
+}
+
+impl VerifyReport {
+    /// Whether every check passed
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+}
+
+#[derive(Deserialize)]
+struct PriceEntry {
+    asset_id: String,
+    stork_signed_price: StorkSignedPrice,
+}
+
+/// Extract signed prices from any of the JSON shapes Stork returns: a `/v1/prices/latest` response or
+/// `oracle_prices` WebSocket message, a single price entry, or a bare `stork_signed_price` object.
+/// `asset_id` is required for bare signed prices, which don't carry one, so that the encoded asset ID
+/// can always be checked.
+pub fn parse_signed_prices(json: &str, asset_id: Option<&str>) -> Result<Vec<SignedPriceEntry>, String> {
+    let value: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| format!("Invalid JSON: {}", e))?;
+
+    let value = match value.get("data") {
+        Some(data) if data.is_object() => data.clone(),
+        _ => value,
+    };
+
+    if value.get("timestamped_signature").is_some() {
+        let signed_price: StorkSignedPrice = serde_json::from_value(value)
+            .map_err(|e| format!("Invalid signed price: {}", e))?;
+        let asset_id = asset_id.ok_or_else(|| {
+            "A bare signed price doesn't include its asset ID; pass it with --asset so the encoded asset ID can be checked".to_string()
+        })?;
+        return Ok(vec![SignedPriceEntry {
+            asset_id: Some(asset_id.to_uppercase()),
+            signed_price,
+        }]);
+    }
+
+    if value.get("stork_signed_price").is_some() {
+        let entry: PriceEntry = serde_json::from_value(value)
+            .map_err(|e| format!("Invalid price entry: {}", e))?;
+        return Ok(vec![SignedPriceEntry {
+            asset_id: Some(entry.asset_id),
+            signed_price: entry.stork_signed_price,
+        }]);
+    }
+
+    let entries: BTreeMap<String, PriceEntry> = serde_json::from_value(value)
+        .map_err(|e| format!("Unrecognized signed price format: {}", e))?;
+    if entries.is_empty() {
+        return Err("No signed prices found in input".to_string());
+    }
+    Ok(entries.into_values()
+        .map(|entry| SignedPriceEntry {
+            asset_id: Some(entry.asset_id),
+            signed_price: entry.stork_signed_price,
+        })
+        .collect())
+}
+
+fn keccak256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut keccak = Keccak::v256();
+    let mut output = [0u8; 32];
+    for part in parts {
+        keccak.update(part);
+    }
+    keccak.finalize(&mut output);
+    output
+}
+
+fn decode_hex(value: &str, field: &str) -> Result<Vec<u8>, String> {
+    let value = value.trim();
+    let value = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")).unwrap_or(value);
+    // Left-pad odd-length values such as "0x1b" shortened to "0xb"
+    let value = if value.len() % 2 == 1 { format!("0{}", value) } else { value.to_string() };
+    hex::decode(value).map_err(|e| format!("Invalid hex in {}: {}", field, e))
+}
+
+fn decode_fixed<const N: usize>(value: &str, field: &str) -> Result<[u8; N], String> {
+    let bytes = decode_hex(value, field)?;
+    if bytes.len() > N {
+        return Err(format!("{} is {} bytes, expected {}", field, bytes.len(), N));
+    }
+    // Left-pad shorter values, as leading zero bytes are sometimes trimmed
+    let mut output = [0u8; N];
+    output[N - bytes.len()..].copy_from_slice(&bytes);
+    Ok(output)
+}
+
+/// Encode a decimal integer string as a 32-byte big-endian two's complement `int256`
+fn encode_int256(value: &str) -> Result<[u8; 32], String> {
+    let (negative, digits) = match value.trim().strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value.trim()),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("Invalid quantized price '{}'", value));
+    }
+
+    let mut output = [0u8; 32];
+    for digit in digits.bytes() {
+        let mut carry = (digit - b'0') as u32;
+        for byte in output.iter_mut().rev() {
+            let value = *byte as u32 * 10 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+        if carry != 0 {
+            return Err(format!("Quantized price '{}' does not fit in int256", value));
+        }
+    }
+
+    if negative {
+        let mut carry = 1u16;
+        for byte in output.iter_mut().rev() {
+            let value = (!*byte) as u16 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+    }
+    Ok(output)
+}
+
+/// EVM address of a secp256k1 public key
+fn address_of(key: &VerifyingKey) -> [u8; 20] {
+    let point = key.to_encoded_point(false);
+    let hash = keccak256(&[&point.as_bytes()[1..]]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+/// Parse a trusted key given either as a 20-byte EVM address or a SEC1-encoded secp256k1 public key
+pub fn parse_public_key(value: &str) -> Result<[u8; 20], String> {
+    let bytes = decode_hex(value, "public key")?;
+    match bytes.len() {
+        20 => {
+            let mut address = [0u8; 20];
+            address.copy_from_slice(&bytes);
+            Ok(address)
+        }
+        33 | 65 => VerifyingKey::from_sec1_bytes(&bytes)
+            .map(|key| address_of(&key))
+            .map_err(|e| format!("Invalid secp256k1 public key: {}", e)),
+        64 => {
+            let mut uncompressed = vec![0x04];
+            uncompressed.extend_from_slice(&bytes);
+            VerifyingKey::from_sec1_bytes(&uncompressed)
+                .map(|key| address_of(&key))
+                .map_err(|e| format!("Invalid secp256k1 public key: {}", e))
+        }
+        n => Err(format!("Public key must be a 20-byte address or a 33/64/65-byte public key, got {} bytes", n)),
+    }
+}
+
+/// The message Stork signs: keccak256 of the packed public key, encoded asset ID, timestamp,
+/// quantized price, publisher merkle root and calculation algorithm checksum
+fn stork_message_hash(public_key: &[u8; 20], signed_price: &StorkSignedPrice, encoded_asset_id: &[u8; 32]) -> Result<[u8; 32], String> {
+    let mut timestamp = [0u8; 32];
+    timestamp[24..].copy_from_slice(&signed_price.timestamped_signature.timestamp.to_be_bytes());
+    let price = encode_int256(&signed_price.price)?;
+    let merkle_root: [u8; 32] = decode_fixed(&signed_price.publisher_merkle_root, "publisher_merkle_root")?;
+    let checksum: [u8; 32] = decode_fixed(&signed_price.calculation_alg.checksum, "calculation_alg.checksum")?;
+
+    Ok(keccak256(&[public_key, encoded_asset_id, &timestamp, &price, &merkle_root, &checksum]))
+}
+
+/// Hash actually passed to ECDSA, following the Ethereum `personal_sign` convention
+fn eth_signed_message_hash(message_hash: &[u8; 32]) -> [u8; 32] {
+    keccak256(&[b"\x19Ethereum Signed Message:\n32", message_hash])
+}
+
+/// Recover the address that produced an (r, s, v) signature over `hash`
+fn recover_signer(hash: &[u8; 32], signature: &EvmSignature) -> Result<[u8; 20], String> {
+    let r: [u8; 32] = decode_fixed(&signature.r, "signature.r")?;
+    let s: [u8; 32] = decode_fixed(&signature.s, "signature.s")?;
+    let v = decode_hex(&signature.v, "signature.v")?;
+    let v = match v.last() {
+        Some(&v) if v >= 27 => v - 27,
+        Some(&v) => v,
+        None => return Err("signature.v is empty".to_string()),
+    };
+
+    let mut signature = Signature::from_scalars(r, s)
+        .map_err(|e| format!("Invalid signature: {}", e))?;
+    let mut recovery_id = RecoveryId::from_byte(v)
+        .ok_or_else(|| format!("Invalid signature recovery id {}", v))?;
+    // Recovery expects a low-s signature; normalizing s flips the parity of the recovered point
+    if let Some(normalized) = signature.normalize_s() {
+        signature = normalized;
+        recovery_id = RecoveryId::new(!recovery_id.is_y_odd(), recovery_id.is_x_reduced());
+    }
+
+    VerifyingKey::recover_from_prehash(hash, &signature, recovery_id)
+        .map(|key| address_of(&key))
+        .map_err(|e| format!("Failed to recover signer: {}", e))
+}
+
+fn format_address(address: &[u8; 20]) -> String {
+    format!("0x{}", hex::encode(address))
+}
+
+/// Verify one signed price against a trusted Stork signer address
+pub fn verify_signed_price(entry: &SignedPriceEntry, trusted_key: &[u8; 20]) -> VerifyReport {
+    let signed_price = &entry.signed_price;
+    let mut checks = Vec::new();
+
+    // The encoded asset ID in the payload must be the keccak hash of the asset ID
+    let encoded_asset_id = match decode_fixed::<32>(&signed_price.encoded_asset_id, "encoded_asset_id") {
+        Ok(encoded) => encoded,
+        Err(e) => {
+            checks.push(Check { name: "encoded asset ID", passed: false, detail: e });
+            return VerifyReport { asset_id: entry.asset_id.clone(), checks };
+        }
+    };
+    if let Some(asset_id) = &entry.asset_id {
+        let expected = calculate_encoded_asset_id(asset_id);
+        let actual = format!("0x{}", hex::encode(encoded_asset_id));
+        checks.push(Check {
+            name: "encoded asset ID",
+            passed: expected == actual,
+            detail: if expected == actual {
+                actual
+            } else {
+                format!("payload has {}, {} encodes to {}", actual, asset_id, expected)
+            },
+        });
+    }
+
+    let payload_key = decode_fixed::<20>(&signed_price.public_key, "public_key");
+    checks.push(match &payload_key {
+        Ok(key) if key == trusted_key => Check {
+            name: "public key",
+            passed: true,
+            detail: format_address(key),
+        },
+        Ok(key) => Check {
+            name: "public key",
+            passed: false,
+            detail: format!("payload names {}, trusted key is {}", format_address(key), format_address(trusted_key)),
+        },
+        Err(e) => Check { name: "public key", passed: false, detail: e.clone() },
+    });
+
+    let message_hash = match stork_message_hash(trusted_key, signed_price, &encoded_asset_id) {
+        Ok(hash) => hash,
+        Err(e) => {
+            checks.push(Check { name: "signature", passed: false, detail: e });
+            return VerifyReport { asset_id: entry.asset_id.clone(), checks };
+        }
+    };
+    let signed_hash = eth_signed_message_hash(&message_hash);
+
+    if let Some(msg_hash) = &signed_price.timestamped_signature.msg_hash {
+        let matches = decode_fixed::<32>(msg_hash, "msg_hash")
+            .map(|hash| hash == message_hash || hash == signed_hash)
+            .unwrap_or(false);
+        checks.push(Check {
+            name: "message hash",
+            passed: matches,
+            detail: if matches {
+                format!("0x{}", hex::encode(message_hash))
+            } else {
+                format!("payload has {}, recomputed 0x{}", msg_hash, hex::encode(message_hash))
+            },
+        });
+    }
+
+    checks.push(match recover_signer(&signed_hash, &signed_price.timestamped_signature.signature) {
+        Ok(signer) if &signer == trusted_key => Check {
+            name: "signature",
+            passed: true,
+            detail: format!("signed by {}", format_address(&signer)),
+        },
+        Ok(signer) => Check {
+            name: "signature",
+            passed: false,
+            detail: format!("recovered signer {} is not the trusted key", format_address(&signer)),
+        },
+        Err(e) => Check { name: "signature", passed: false, detail: e },
+    });
+
+    VerifyReport { asset_id: entry.asset_id.clone(), checks }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Address of the key that signed [`LATEST_PRICES`]
+    const SIGNER: &str = "0x17c5185167401ed00cf5f5b2fc97d9bbfdb7d025";
+
+    /// A `/v1/prices/latest` response for BTCUSD, signed by a test key in place of Stork's
+    const LATEST_PRICES: &str = r#"{
+        "data": {
+            "BTCUSD": {
+                "timestamp": 1730000000123456789,
+                "asset_id": "BTCUSD",
+                "signature_type": "evm",
+                "trigger": "deviation",
+                "price": "67900500000000000000000",
+                "stork_signed_price": {
+                    "public_key": "0x17c5185167401ed00cf5f5b2fc97d9bbfdb7d025",
+                    "encoded_asset_id": "0x7404e3d104ea7841c3d9e6fd20adfe99b4ad586bc08d8f3bd3afef894cf184de",
+                    "price": "67900500000000000000000",
+                    "timestamped_signature": {
+                        "signature": {
+                            "r": "0xdfbe067ab63862245d10a2011d9c1d137852c20af387be20cd0cd649c3871465",
+                            "s": "0x390f3c989c1324d22fd13087ae6ea35255fb2b2651d8f2496152502fff1a5974",
+                            "v": "0x1b"
+                        },
+                        "timestamp": 1730000000123456789,
+                        "msg_hash": "0x95c36c8c8b4292197ea2d168813926da02bb0672043e9997d96643100b22a39e"
+                    },
+                    "publisher_merkle_root": "0x5a6f2ba4ba4b2e8e1a4ef4d1fd4d10e1fa8f3c0e5d5b43b3c0fb3f7e2d1c8a90",
+                    "calculation_alg": {
+                        "type": "median",
+                        "version": "v1",
+                        "checksum": "0x9be7e9f9ed459417d96112a7467bd0b27575a2c7847195c68f805b70ce1795ba"
+                    }
+                }
+            }
+        }
+    }"#;
+
+    fn verify(json: &str, asset_id: Option<&str>, trusted_key: &str) -> VerifyReport {
+        let entries = parse_signed_prices(json, asset_id).unwrap();
+        assert_eq!(entries.len(), 1);
+        verify_signed_price(&entries[0], &parse_public_key(trusted_key).unwrap())
+    }
+
+    fn failed_checks(report: &VerifyReport) -> Vec<&str> {
+        report.checks.iter().filter(|check| !check.passed).map(|check| check.name).collect()
+    }
+
+    #[test]
+    fn latest_prices_payload_verifies() {
+        let report = verify(LATEST_PRICES, None, SIGNER);
+        assert_eq!(report.asset_id.as_deref(), Some("BTCUSD"));
+        assert!(report.passed(), "failed: {:?}", failed_checks(&report));
+        let names: Vec<&str> = report.checks.iter().map(|check| check.name).collect();
+        assert_eq!(names, ["encoded asset ID", "public key", "message hash", "signature"]);
+        // The compressed public key identifies the same signer as its address
+        let compressed = "0324653eac434488002cc06bbfb7f10fe18991e35f9fe4302dbea6d2353dc0ab1c";
+        assert!(verify(LATEST_PRICES, None, compressed).passed());
+    }
+
+    #[test]
+    fn tampered_price_fails() {
+        let tampered = LATEST_PRICES.replace(
+            r#""price": "67900500000000000000000",
+                    "timestamped_signature""#,
+            r#""price": "67900600000000000000000",
+                    "timestamped_signature""#,
+        );
+        assert_ne!(tampered, LATEST_PRICES);
+        let report = verify(&tampered, None, SIGNER);
+        assert!(!report.passed());
+        assert_eq!(failed_checks(&report), ["message hash", "signature"]);
+    }
+
+    #[test]
+    fn wrong_trusted_key_fails() {
+        let report = verify(LATEST_PRICES, None, "0x0a803f9b1cce32e2773e0d2e98b37e0775ca5d44");
+        assert!(!report.passed());
+        assert!(failed_checks(&report).contains(&"public key"));
+        assert!(failed_checks(&report).contains(&"signature"));
+    }
+
+    #[test]
+    fn bare_signed_price_needs_asset() {
+        let value: serde_json::Value = serde_json::from_str(LATEST_PRICES).unwrap();
+        let bare = value["data"]["BTCUSD"]["stork_signed_price"].to_string();
+
+        let err = parse_signed_prices(&bare, None).err().unwrap();
+        assert!(err.contains("--asset"), "{}", err);
+
+        assert!(verify(&bare, Some("btcusd"), SIGNER).passed());
+        let report = verify(&bare, Some("ETHUSD"), SIGNER);
+        assert_eq!(failed_checks(&report), ["encoded asset ID"]);
+    }
+}